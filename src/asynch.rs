@@ -0,0 +1,269 @@
+use crate::{FlashMock, MultiwriteFlashMock};
+use embedded_storage::nor_flash as blocking;
+use embedded_storage_async::nor_flash::{MultiwriteNorFlash, NorFlash, ReadNorFlash};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+    time::Duration,
+};
+
+// ------------------------------
+// 1. 异步版ReadNorFlash/NorFlash（`async` feature）
+// 与阻塞版共用核心实现，检查、掉电注入、统计等行为完全一致；
+// 耗时模型的等待改为不阻塞执行器的定时器
+// ------------------------------
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> ReadNorFlash
+    for FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+    const READ_SIZE: usize = READ_SIZE;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let result = self.read_op(offset, bytes);
+        Delay::new(self.take_pending_busy()).await;
+        result
+    }
+
+    fn capacity(&self) -> usize {
+        blocking::ReadNorFlash::capacity(self)
+    }
+}
+
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> NorFlash
+    for FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+    const WRITE_SIZE: usize = WRITE_SIZE;
+    const ERASE_SIZE: usize = ERASE_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        let result = self.erase_op(from, to);
+        Delay::new(self.take_pending_busy()).await;
+        result
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let result = self.write_op(offset, bytes);
+        Delay::new(self.take_pending_busy()).await;
+        result
+    }
+}
+
+/// 不依赖具体运行时的定时器：由后台线程睡眠到期后唤醒任务
+struct Delay {
+    duration: Duration,
+    state: Option<Arc<Mutex<DelayState>>>,
+}
+
+/// 定时器线程与任务共享的状态
+#[derive(Default)]
+struct DelayState {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+impl Delay {
+    fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            state: None,
+        }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.duration.is_zero() {
+            return Poll::Ready(());
+        }
+        let duration = self.duration;
+        let state = self.state.get_or_insert_with(|| {
+            // 首次poll时启动定时器线程
+            let state = Arc::new(Mutex::new(DelayState::default()));
+            let shared = Arc::clone(&state);
+            thread::spawn(move || {
+                thread::sleep(duration);
+                let mut state = shared.lock().unwrap();
+                state.done = true;
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            });
+            state
+        });
+        let mut state = state.lock().unwrap();
+        if state.done {
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+// ------------------------------
+// 2. MultiwriteFlashMock的异步实现
+// ------------------------------
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> ReadNorFlash
+    for MultiwriteFlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+    const READ_SIZE: usize = READ_SIZE;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        ReadNorFlash::read(&mut self.0, offset, bytes).await
+    }
+
+    fn capacity(&self) -> usize {
+        ReadNorFlash::capacity(&self.0)
+    }
+}
+
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> NorFlash
+    for MultiwriteFlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+    const WRITE_SIZE: usize = WRITE_SIZE;
+    const ERASE_SIZE: usize = ERASE_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        NorFlash::erase(&mut self.0, from, to).await
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        NorFlash::write(&mut self.0, offset, bytes).await
+    }
+}
+
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> MultiwriteNorFlash
+    for MultiwriteFlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+}
+
+// ------------------------------
+// 3. 异步版读-改-写Storage适配器
+// embedded-storage-async没有Storage trait，这里提供与阻塞版
+// RmwNorFlashStorage/RmwMultiwriteNorFlashStorage行为一致的异步方法
+// ------------------------------
+/// 异步版`RmwNorFlashStorage`：在任意异步`NorFlash`上按字节写入，自动处理“读-擦-写”
+pub struct AsyncRmwNorFlashStorage<'a, S> {
+    storage: S,
+    merge_buffer: &'a mut [u8],
+}
+
+impl<'a, S: NorFlash> AsyncRmwNorFlashStorage<'a, S> {
+    /// 创建适配器（`merge_buffer`小于ERASE_SIZE时panic，与阻塞版一致）
+    pub fn new(nor_flash: S, merge_buffer: &'a mut [u8]) -> Self {
+        assert!(
+            merge_buffer.len() >= S::ERASE_SIZE,
+            "Merge buffer is too small"
+        );
+        Self {
+            storage: nor_flash,
+            merge_buffer,
+        }
+    }
+
+    /// 取回底层Flash
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+
+    /// 总存储容量
+    pub fn capacity(&self) -> usize {
+        self.storage.capacity()
+    }
+
+    /// 读取数据（直接转发给底层Flash）
+    pub async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), S::Error> {
+        self.storage.read(offset, bytes).await
+    }
+
+    /// 按字节写入，每个涉及的擦除块都会被读出、擦除、合并后整块写回
+    pub async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), S::Error> {
+        rmw_write(&mut self.storage, self.merge_buffer, offset, bytes, false).await
+    }
+}
+
+/// 异步版`RmwMultiwriteNorFlashStorage`：新数据只需把1改为0时原地写入，否则退回“读-擦-写”
+pub struct AsyncRmwMultiwriteNorFlashStorage<'a, S> {
+    storage: S,
+    merge_buffer: &'a mut [u8],
+}
+
+impl<'a, S: MultiwriteNorFlash> AsyncRmwMultiwriteNorFlashStorage<'a, S> {
+    /// 创建适配器（`merge_buffer`小于ERASE_SIZE时panic，与阻塞版一致）
+    pub fn new(nor_flash: S, merge_buffer: &'a mut [u8]) -> Self {
+        assert!(
+            merge_buffer.len() >= S::ERASE_SIZE,
+            "Merge buffer is too small"
+        );
+        Self {
+            storage: nor_flash,
+            merge_buffer,
+        }
+    }
+
+    /// 取回底层Flash
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+
+    /// 总存储容量
+    pub fn capacity(&self) -> usize {
+        self.storage.capacity()
+    }
+
+    /// 读取数据（直接转发给底层Flash）
+    pub async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), S::Error> {
+        self.storage.read(offset, bytes).await
+    }
+
+    /// 按字节写入，能原地编程时只写入涉及的字，否则整块“读-擦-写”
+    pub async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), S::Error> {
+        rmw_write(&mut self.storage, self.merge_buffer, offset, bytes, true).await
+    }
+}
+
+/// 逐个擦除块执行读-改-写；`in_place`为true时，若新数据是旧数据的位子集则跳过擦除
+async fn rmw_write<S: NorFlash>(
+    storage: &mut S,
+    merge_buffer: &mut [u8],
+    offset: u32,
+    bytes: &[u8],
+    in_place: bool,
+) -> Result<(), S::Error> {
+    let page_buffer = &mut merge_buffer[..S::ERASE_SIZE];
+    let mut done = 0;
+    while done < bytes.len() {
+        let address = offset as usize + done;
+        let page_start = address - address % S::ERASE_SIZE;
+        let offset_into_page = address - page_start;
+        let length = (S::ERASE_SIZE - offset_into_page).min(bytes.len() - done);
+        let data = &bytes[done..done + length];
+
+        storage.read(page_start as u32, page_buffer).await?;
+        let old = &page_buffer[offset_into_page..offset_into_page + length];
+        let is_subset = data.iter().zip(old).all(|(&new, &old)| new & old == new);
+
+        if in_place && is_subset {
+            // 用0xFF把数据补齐到WRITE_SIZE对齐，按位与后不影响其余位
+            let start = offset_into_page - offset_into_page % S::WRITE_SIZE;
+            let end = (offset_into_page + length).next_multiple_of(S::WRITE_SIZE);
+            page_buffer[start..end].fill(0xFF);
+            page_buffer[offset_into_page..offset_into_page + length].copy_from_slice(data);
+            storage
+                .write((page_start + start) as u32, &page_buffer[start..end])
+                .await?;
+        } else {
+            storage
+                .erase(page_start as u32, (page_start + S::ERASE_SIZE) as u32)
+                .await?;
+            page_buffer[offset_into_page..offset_into_page + length].copy_from_slice(data);
+            storage.write(page_start as u32, page_buffer).await?;
+        }
+        done += length;
+    }
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+/// `fill`/`is_filled`默认实现一次处理的最大块大小，避免大区域操作时分配整段缓冲
+const CHUNK: usize = 4096;
+
+/// Flash数据的后端存储接口
+///
+/// 后端只负责按字节偏移读写原始数据；对齐/边界检查、先擦后写、掉电注入等NOR语义
+/// 统一由`FlashMock`处理，调用方保证访问范围不超过创建时给定的容量。
+pub trait FlashBackend: Send {
+    /// 从`offset`处读取`buf.len()`字节
+    fn read_at(&mut self, offset: u32, buf: &mut [u8]) -> io::Result<()>;
+
+    /// 在`offset`处写入`data`
+    fn write_at(&mut self, offset: u32, data: &[u8]) -> io::Result<()>;
+
+    /// 将`[offset, offset+length)`全部填充为`value`
+    fn fill(&mut self, offset: u32, length: usize, value: u8) -> io::Result<()> {
+        let chunk = vec![value; length.min(CHUNK)];
+        let mut done = 0;
+        while done < length {
+            let step = (length - done).min(chunk.len());
+            self.write_at(offset + done as u32, &chunk[..step])?;
+            done += step;
+        }
+        Ok(())
+    }
+
+    /// 检查`[offset, offset+length)`是否全部为`value`
+    fn is_filled(&mut self, offset: u32, length: usize, value: u8) -> io::Result<bool> {
+        let mut chunk = vec![0u8; length.min(CHUNK)];
+        let mut done = 0;
+        while done < length {
+            let step = (length - done).min(chunk.len());
+            self.read_at(offset + done as u32, &mut chunk[..step])?;
+            if chunk[..step].iter().any(|&byte| byte != value) {
+                return Ok(false);
+            }
+            done += step;
+        }
+        Ok(true)
+    }
+
+    /// 同步数据到持久化介质
+    fn sync(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// 整个存储内容的零拷贝视图（仅内存类后端支持）
+    fn as_slice(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+/// 文件后端：每次访问执行seek + read/write
+pub struct FileBackend {
+    file: File,
+}
+
+impl FileBackend {
+    /// 使用已打开的文件（需同时具有读写权限）
+    pub fn new(file: File) -> Self {
+        Self { file }
+    }
+}
+
+impl FlashBackend for FileBackend {
+    fn read_at(&mut self, offset: u32, buf: &mut [u8]) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(offset as u64))?;
+        self.file.read_exact(buf)
+    }
+
+    fn write_at(&mut self, offset: u32, data: &[u8]) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(offset as u64))?;
+        self.file.write_all(data)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+}
+
+/// 内存后端：数据保存在`Vec<u8>`中
+pub struct MemoryBackend {
+    image: Vec<u8>,
+}
+
+impl MemoryBackend {
+    /// 使用已有的镜像数据
+    pub fn new(image: Vec<u8>) -> Self {
+        Self { image }
+    }
+
+    /// 取回镜像数据
+    pub fn into_inner(self) -> Vec<u8> {
+        self.image
+    }
+
+    fn range_mut(&mut self, offset: u32, length: usize) -> io::Result<&mut [u8]> {
+        let start = offset as usize;
+        self.image
+            .get_mut(start..start + length)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))
+    }
+}
+
+impl FlashBackend for MemoryBackend {
+    fn read_at(&mut self, offset: u32, buf: &mut [u8]) -> io::Result<()> {
+        buf.copy_from_slice(self.range_mut(offset, buf.len())?);
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u32, data: &[u8]) -> io::Result<()> {
+        self.range_mut(offset, data.len())?.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn fill(&mut self, offset: u32, length: usize, value: u8) -> io::Result<()> {
+        self.range_mut(offset, length)?.fill(value);
+        Ok(())
+    }
+
+    fn is_filled(&mut self, offset: u32, length: usize, value: u8) -> io::Result<bool> {
+        Ok(self
+            .range_mut(offset, length)?
+            .iter()
+            .all(|&byte| byte == value))
+    }
+
+    fn as_slice(&self) -> Option<&[u8]> {
+        Some(&self.image)
+    }
+}
@@ -0,0 +1,33 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+/// 可共享的虚拟时钟（纳秒精度）
+///
+/// 克隆得到的句柄共享同一个时间，可交给测试调度器查询或推进。
+#[derive(Debug, Clone, Default)]
+pub struct VirtualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl VirtualClock {
+    /// 创建从零开始的虚拟时钟
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 当前时间
+    pub fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Acquire))
+    }
+
+    /// 推进时间
+    pub fn advance(&self, duration: Duration) {
+        self.nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{FlashMock, FlashMockError, PowerCut};
+use thiserror::Error;
+
+/// 穷举掉电点的统计结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrashExploration {
+    /// 被测闭包发出的write/erase总次数（即验证过的掉电点数量）
+    pub cut_points: usize,
+}
+
+/// 穷举掉电点失败的原因
+#[derive(Debug, Error)]
+pub enum CrashExplorationError<E> {
+    #[error("Failed to save or restore flash state: {0}")]
+    Flash(#[from] FlashMockError),
+    #[error("Recovery check failed at cut point {cut_point}: {error}")]
+    Verify { cut_point: usize, error: E },
+}
+
+/// 穷举所有掉电点，验证被测逻辑的掉电一致性
+///
+/// 流程：
+/// 1. 保存`flash`的初始状态快照（内容、磨损计数、字编程记录），完整运行一次`run`，
+///    统计其发出的write/erase次数N
+/// 2. 对每个掉电点`cut_point`（0..N）：恢复初始状态，以`PowerCut::AfterOps(cut_point)`
+///    重新运行`run`（第`cut_point`次操作执行到一半时掉电），随后模拟重新上电，
+///    调用`verify(flash, cut_point)`检查恢复后的镜像
+/// 3. 任一掉电点验证失败立即返回`CrashExplorationError::Verify`；结束后恢复初始状态
+///
+/// `run`必须是确定性的（相同镜像上发出相同的操作序列），其返回值被忽略。
+pub fn explore_power_cuts<
+    const READ_SIZE: usize,
+    const WRITE_SIZE: usize,
+    const ERASE_SIZE: usize,
+    T,
+    E,
+>(
+    flash: &mut FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>,
+    mut run: impl FnMut(&mut FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>) -> T,
+    mut verify: impl FnMut(&mut FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>, usize) -> Result<(), E>,
+) -> Result<CrashExploration, CrashExplorationError<E>> {
+    // 用快照保存完整状态，避免磨损计数等在各次运行之间累积
+    let initial = flash.snapshot()?;
+
+    // 完整运行一次，统计write/erase次数
+    flash.set_power_cut(None);
+    run(flash);
+    let cut_points = flash.cut_ops;
+
+    for cut_point in 0..cut_points {
+        flash.power_cycle();
+        flash.restore(&initial)?;
+        flash.set_power_cut(Some(PowerCut::AfterOps(cut_point)));
+        run(flash);
+
+        // 重新上电后交给调用者检查恢复逻辑
+        flash.set_power_cut(None);
+        flash.power_cycle();
+        verify(flash, cut_point)
+            .map_err(|error| CrashExplorationError::Verify { cut_point, error })?;
+    }
+
+    flash.restore(&initial)?;
+    Ok(CrashExploration { cut_points })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProgramMode;
+    use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+    #[test]
+    fn explorer_sees_torn_ecc_word() {
+        let mut flash = FlashMock::<1, 4, 256>::new_in_memory(1024).unwrap();
+        flash.set_program_mode(ProgramMode::Ecc);
+        // 初始状态中字0已编程：每次运行前必须连同ECC状态一起恢复
+        flash.write(0, &[0x11; 4]).unwrap();
+
+        let mut torn = Vec::new();
+        let exploration = explore_power_cuts(
+            &mut flash,
+            |flash| {
+                flash.erase(0, 256)?;
+                flash.write(0, &[0xA5; 8])
+            },
+            |flash, cut_point| {
+                let mut buf = [0u8; 8];
+                if let Err(FlashMockError::EccError { offset }) = flash.read(0, &mut buf) {
+                    torn.push((cut_point, offset));
+                }
+                Ok::<(), ()>(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(exploration.cut_points, 2);
+        assert_eq!(torn, [(1, 4)]);
+
+        // 结束后恢复初始状态：内容与ECC记录都回到运行前
+        let mut buf = [0u8; 4];
+        flash.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [0x11; 4]);
+        assert!(matches!(
+            flash.write(0, &[0x22; 4]),
+            Err(FlashMockError::WordAlreadyProgrammed { offset: 0 })
+        ));
+    }
+}
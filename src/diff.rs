@@ -0,0 +1,205 @@
+use crate::ErasedValue;
+use std::{fmt, ops::Range};
+
+/// 擦除块的变化类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockChange {
+    /// 块被擦除（新内容全为擦除值）
+    Erased,
+    /// 只有从擦除态到编程态的位翻转，无需擦除即可得到新内容
+    Programmed,
+    /// 有位从编程态回到擦除态，需要先擦除再编程
+    Rewritten,
+}
+
+impl fmt::Display for BlockChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockChange::Erased => f.write_str("erased"),
+            BlockChange::Programmed => f.write_str("programmed"),
+            BlockChange::Rewritten => f.write_str("rewritten"),
+        }
+    }
+}
+
+/// 一个擦除块的差异
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockDiff {
+    /// 块编号
+    pub block: usize,
+    /// 块起始地址
+    pub offset: u32,
+    /// 变化类型
+    pub change: BlockChange,
+    /// 块内不同的地址范围（按WRITE_SIZE字对齐，相邻的字合并）
+    pub ranges: Vec<Range<u32>>,
+}
+
+/// 两个镜像之间按擦除块划分的差异，`Display`输出可读的报告
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashDiff {
+    /// 最小写入单位
+    pub write_size: usize,
+    /// 最小擦除单位
+    pub erase_size: usize,
+    /// 镜像中的擦除块总数
+    pub total_blocks: usize,
+    /// 有差异的块（按地址排序）
+    pub blocks: Vec<BlockDiff>,
+}
+
+impl FlashDiff {
+    /// 比较`old`与`new`两个镜像
+    ///
+    /// # Panics
+    /// 两个镜像长度不同，或长度不是`erase_size`的整数倍时panic。
+    pub fn new(
+        old: &[u8],
+        new: &[u8],
+        write_size: usize,
+        erase_size: usize,
+        erased_value: ErasedValue,
+    ) -> Self {
+        assert_eq!(old.len(), new.len(), "images must have the same length");
+        assert!(
+            old.len().is_multiple_of(erase_size),
+            "image length must be a multiple of erase_size"
+        );
+        let erased = erased_value.byte();
+        let blocks = old
+            .chunks(erase_size)
+            .zip(new.chunks(erase_size))
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(block, (old, new))| {
+                let offset = (block * erase_size) as u32;
+                let change = if new.iter().all(|&byte| byte == erased) {
+                    BlockChange::Erased
+                } else if old
+                    .iter()
+                    .zip(new)
+                    .all(|(&old, &new)| erased_value.program(old, new) == new)
+                {
+                    BlockChange::Programmed
+                } else {
+                    BlockChange::Rewritten
+                };
+                BlockDiff {
+                    block,
+                    offset,
+                    change,
+                    ranges: word_ranges(old, new, offset, write_size),
+                }
+            })
+            .collect();
+        Self {
+            write_size,
+            erase_size,
+            total_blocks: old.len() / erase_size,
+            blocks,
+        }
+    }
+
+    /// 两个镜像是否完全相同
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+/// 块内不同的WRITE_SIZE字，相邻的合并为一个范围
+fn word_ranges(old: &[u8], new: &[u8], offset: u32, write_size: usize) -> Vec<Range<u32>> {
+    let mut ranges: Vec<Range<u32>> = Vec::new();
+    for (word, (old, new)) in old
+        .chunks(write_size)
+        .zip(new.chunks(write_size))
+        .enumerate()
+    {
+        if old == new {
+            continue;
+        }
+        let start = offset + (word * write_size) as u32;
+        let end = start + old.len() as u32;
+        match ranges.last_mut() {
+            Some(last) if last.end == start => last.end = end,
+            _ => ranges.push(start..end),
+        }
+    }
+    ranges
+}
+
+impl fmt::Display for FlashDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} erase blocks differ (erase size {}, write size {})",
+            self.blocks.len(),
+            self.total_blocks,
+            self.erase_size,
+            self.write_size
+        )?;
+        for block in &self.blocks {
+            writeln!(
+                f,
+                "block {} @ {:#010x}: {}",
+                block.block, block.offset, block.change
+            )?;
+            for range in &block.ranges {
+                writeln!(
+                    f,
+                    "  {:#010x}..{:#010x} ({} bytes)",
+                    range.start,
+                    range.end,
+                    range.end - range.start
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FlashMock, FlashMockError};
+
+    #[test]
+    fn classifies_blocks() {
+        let old = [[0xFF; 16], [0x0F; 16], [0x0F; 16], [0x00; 16]].concat();
+        let mut new = old.clone();
+        new[20] = 0x0E; // 块1：只有1→0
+        new[36..44].fill(0xF0); // 块2：有0→1
+        new[48..64].fill(0xFF); // 块3：擦除
+        let diff = FlashDiff::new(&old, &new, 4, 16, ErasedValue::Ones);
+        let changes: Vec<_> = diff
+            .blocks
+            .iter()
+            .map(|block| (block.block, block.change))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (1, BlockChange::Programmed),
+                (2, BlockChange::Rewritten),
+                (3, BlockChange::Erased),
+            ]
+        );
+        assert_eq!(diff.blocks[0].ranges, [Range { start: 20, end: 24 }]);
+        assert_eq!(diff.blocks[1].ranges, [Range { start: 36, end: 44 }]);
+    }
+
+    #[test]
+    fn diff_images_rejects_wrong_lengths() {
+        let flash = FlashMock::<1, 4, 256>::new_in_memory(1024).unwrap();
+        let image = vec![0xFF; 1024];
+        assert!(flash.diff_images(&image, &image).unwrap().is_empty());
+        for (old, new) in [
+            (&image[..1000], &image[..1000]),
+            (&image[..], &image[..768]),
+        ] {
+            assert!(matches!(
+                flash.diff_images(old, new),
+                Err(FlashMockError::ImageSizeMismatch { expected: 1024, .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::mix64;
+
+/// 位翻转的作用方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitFlipMode {
+    /// 只翻转本次读取返回的数据，存储内容保持不变
+    #[default]
+    Transient,
+    /// 翻转存储的数据（读干扰）：按误码率产生的翻转在读取时写回存储，
+    /// 指定地址的翻转在设置时立即写入存储
+    Persistent,
+}
+
+/// 位翻转故障模型（相同的种子与操作序列产生相同的翻转）
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BitFlips {
+    /// 伪随机数种子
+    pub seed: u64,
+    /// 误码率：每读取一位发生翻转的概率（0.0表示不随机翻转）
+    pub bit_error_rate: f64,
+    /// 指定翻转的位（地址 -> 位掩码）
+    pub targets: Vec<(u32, u8)>,
+    /// 作用方式
+    pub mode: BitFlipMode,
+}
+
+/// splitmix64伪随机数发生器
+#[derive(Debug, Clone)]
+pub(crate) struct FlipRng {
+    state: u64,
+}
+
+impl FlipRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let value = mix64(self.state);
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        value
+    }
+
+    /// (0, 1]内的均匀分布
+    fn next_unit(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    /// 按误码率翻转`bytes`中的位，返回是否有位被翻转
+    ///
+    /// 用几何分布直接跳到下一个翻转位，不必逐位抽样。
+    pub(crate) fn flip(&mut self, bytes: &mut [u8], bit_error_rate: f64) -> bool {
+        if bit_error_rate <= 0.0 || bytes.is_empty() {
+            return false;
+        }
+        let total_bits = bytes.len() as u64 * 8;
+        let log_keep = (1.0 - bit_error_rate.min(1.0)).ln();
+        if log_keep == 0.0 {
+            // 误码率小到无法与0区分
+            return false;
+        }
+        let mut flipped = false;
+        let mut bit = 0u64;
+        loop {
+            let gap = (self.next_unit().ln() / log_keep).floor();
+            if gap >= (total_bits - bit) as f64 {
+                return flipped;
+            }
+            bit += gap as u64;
+            bytes[(bit / 8) as usize] ^= 1 << (bit % 8);
+            flipped = true;
+            bit += 1;
+            if bit >= total_bits {
+                return flipped;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FlashMock, FlashMockError};
+    use embedded_storage::nor_flash::{NorFlashErrorKind, ReadNorFlash};
+
+    fn read(flash: &mut FlashMock<1, 4, 256>) -> Vec<u8> {
+        let mut buf = vec![0u8; 1024];
+        flash.read(0, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn out_of_range_targets_change_nothing() {
+        for mode in [BitFlipMode::Transient, BitFlipMode::Persistent] {
+            let mut flash = FlashMock::<1, 4, 256>::new_in_memory(1024).unwrap();
+            let previous = BitFlips {
+                targets: vec![(5, 0x10)],
+                ..Default::default()
+            };
+            flash.set_bit_flips(Some(previous)).unwrap();
+            let faults = BitFlips {
+                targets: vec![(0, 0x01), (1024, 0x01)],
+                mode,
+                ..Default::default()
+            };
+            assert!(matches!(
+                flash.set_bit_flips(Some(faults)),
+                Err(FlashMockError::CheckFailed(NorFlashErrorKind::OutOfBounds))
+            ));
+            // 存储未被改动，原有的位翻转配置仍然生效
+            let data = read(&mut flash);
+            assert_eq!(data[5], 0xEF);
+            assert!(
+                data.iter()
+                    .enumerate()
+                    .all(|(i, &byte)| i == 5 || byte == 0xFF)
+            );
+        }
+    }
+
+    #[test]
+    fn targets_flip_in_both_modes() {
+        let mut flash = FlashMock::<1, 4, 256>::new_in_memory(1024).unwrap();
+        let faults = BitFlips {
+            targets: vec![(3, 0x81)],
+            ..Default::default()
+        };
+        flash.set_bit_flips(Some(faults.clone())).unwrap();
+        assert_eq!(read(&mut flash)[3], 0x7E);
+        // 瞬时翻转不改变存储内容
+        flash.set_bit_flips(None).unwrap();
+        assert_eq!(read(&mut flash)[3], 0xFF);
+
+        let persistent = BitFlips {
+            mode: BitFlipMode::Persistent,
+            ..faults
+        };
+        flash.set_bit_flips(Some(persistent)).unwrap();
+        flash.set_bit_flips(None).unwrap();
+        assert_eq!(read(&mut flash)[3], 0x7E);
+    }
+}
@@ -0,0 +1,694 @@
+use crate::{FlashMock, FlashMockError};
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+use std::{collections::BTreeMap, fmt::Write as _, path::Path};
+use thiserror::Error;
+
+/// UF2块的魔数与标志
+const UF2_MAGIC_START0: u32 = 0x0A32_4655;
+const UF2_MAGIC_START1: u32 = 0x9E5D_5157;
+const UF2_MAGIC_END: u32 = 0x0AB1_6F30;
+const UF2_FLAG_NOT_MAIN_FLASH: u32 = 0x0000_0001;
+const UF2_BLOCK_SIZE: usize = 512;
+const UF2_PAYLOAD_SIZE: usize = 256;
+
+/// 导出时每条HEX/S-record记录的数据字节数
+const RECORD_SIZE: usize = 16;
+
+/// 固件镜像格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Intel HEX（`.hex`/`.ihex`）
+    IntelHex,
+    /// Motorola S-record（`.srec`/`.s19`/`.s28`/`.s37`/`.mot`）
+    SRecord,
+    /// Microsoft UF2（`.uf2`）
+    Uf2,
+}
+
+impl ImageFormat {
+    /// 按文件扩展名推断格式
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "hex" | "ihex" => Some(ImageFormat::IntelHex),
+            "srec" | "s19" | "s28" | "s37" | "mot" => Some(ImageFormat::SRecord),
+            "uf2" => Some(ImageFormat::Uf2),
+            _ => None,
+        }
+    }
+}
+
+/// 固件镜像导入/导出错误
+#[derive(Error, Debug)]
+pub enum FirmwareError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid firmware record {record}: {reason}")]
+    Parse { record: usize, reason: &'static str },
+    #[error("Address outside the flash window (address: {address:#x})")]
+    OutOfRange { address: u64 },
+    #[error("Flash error: {0}")]
+    Flash(#[from] FlashMockError),
+}
+
+/// 解析出的一段连续数据（绝对地址 + 数据）
+pub(crate) type Segment = (u64, Vec<u8>);
+
+/// 解析固件镜像文件
+pub(crate) fn parse(bytes: &[u8], format: ImageFormat) -> Result<Vec<Segment>, FirmwareError> {
+    match format {
+        ImageFormat::IntelHex => parse_intel_hex(bytes),
+        ImageFormat::SRecord => parse_srecord(bytes),
+        ImageFormat::Uf2 => parse_uf2(bytes),
+    }
+}
+
+/// 把一行十六进制文本解码为字节
+fn decode_hex(text: &str, record: usize) -> Result<Vec<u8>, FirmwareError> {
+    let invalid = FirmwareError::Parse {
+        record,
+        reason: "invalid hex digits",
+    };
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err(invalid);
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&text[index..index + 2], 16))
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid)
+}
+
+fn parse_intel_hex(bytes: &[u8]) -> Result<Vec<Segment>, FirmwareError> {
+    let text = std::str::from_utf8(bytes).map_err(|_| FirmwareError::Parse {
+        record: 0,
+        reason: "not a text file",
+    })?;
+    let mut segments = Vec::new();
+    let mut upper = 0u64; // 扩展段地址或扩展线性地址
+    for (index, line) in text.lines().enumerate() {
+        let record = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parse_error = |reason| FirmwareError::Parse { record, reason };
+        let body = line.strip_prefix(':').ok_or(parse_error("missing ':'"))?;
+        let raw = decode_hex(body, record)?;
+        if raw.len() < 5 || raw.len() != raw[0] as usize + 5 {
+            return Err(parse_error("bad record length"));
+        }
+        if raw.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) != 0 {
+            return Err(parse_error("checksum mismatch"));
+        }
+        let address = u16::from_be_bytes([raw[1], raw[2]]) as u64;
+        let data = &raw[4..raw.len() - 1];
+        match raw[3] {
+            0x00 => segments.push((upper + address, data.to_vec())),
+            0x01 => break,
+            0x02 if data.len() == 2 => upper = (u16::from_be_bytes([data[0], data[1]]) as u64) << 4,
+            0x04 if data.len() == 2 => {
+                upper = (u16::from_be_bytes([data[0], data[1]]) as u64) << 16
+            }
+            0x03 | 0x05 => {} // 起始地址，与Flash内容无关
+            _ => return Err(parse_error("unsupported record type")),
+        }
+    }
+    Ok(segments)
+}
+
+fn parse_srecord(bytes: &[u8]) -> Result<Vec<Segment>, FirmwareError> {
+    let text = std::str::from_utf8(bytes).map_err(|_| FirmwareError::Parse {
+        record: 0,
+        reason: "not a text file",
+    })?;
+    let mut segments = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let record = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parse_error = |reason| FirmwareError::Parse { record, reason };
+        let mut chars = line.chars();
+        if chars.next() != Some('S') {
+            return Err(parse_error("missing 'S'"));
+        }
+        let kind = chars.next().ok_or(parse_error("missing record type"))?;
+        let raw = decode_hex(chars.as_str(), record)?;
+        if raw.is_empty() || raw.len() != raw[0] as usize + 1 {
+            return Err(parse_error("bad record length"));
+        }
+        if raw.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) != 0xFF {
+            return Err(parse_error("checksum mismatch"));
+        }
+        let address_size = match kind {
+            '1' => 2,
+            '2' => 3,
+            '3' => 4,
+            '0' | '5' | '6' | '7' | '8' | '9' => continue, // 头部、计数与起始地址
+            _ => return Err(parse_error("unsupported record type")),
+        };
+        if raw.len() < address_size + 2 {
+            return Err(parse_error("bad record length"));
+        }
+        let address = raw[1..1 + address_size]
+            .iter()
+            .fold(0u64, |address, &byte| address << 8 | byte as u64);
+        segments.push((address, raw[1 + address_size..raw.len() - 1].to_vec()));
+    }
+    Ok(segments)
+}
+
+fn parse_uf2(bytes: &[u8]) -> Result<Vec<Segment>, FirmwareError> {
+    if !bytes.len().is_multiple_of(UF2_BLOCK_SIZE) {
+        return Err(FirmwareError::Parse {
+            record: bytes.len() / UF2_BLOCK_SIZE + 1,
+            reason: "truncated UF2 block",
+        });
+    }
+    let mut segments = Vec::new();
+    for (index, block) in bytes.chunks(UF2_BLOCK_SIZE).enumerate() {
+        let parse_error = |reason| FirmwareError::Parse {
+            record: index + 1,
+            reason,
+        };
+        let word = |at: usize| u32::from_le_bytes(block[at..at + 4].try_into().unwrap());
+        if word(0) != UF2_MAGIC_START0
+            || word(4) != UF2_MAGIC_START1
+            || word(UF2_BLOCK_SIZE - 4) != UF2_MAGIC_END
+        {
+            return Err(parse_error("bad UF2 magic"));
+        }
+        if word(8) & UF2_FLAG_NOT_MAIN_FLASH != 0 {
+            continue;
+        }
+        let size = word(16) as usize;
+        if size > 476 {
+            return Err(parse_error("bad UF2 payload size"));
+        }
+        segments.push((word(12) as u64, block[32..32 + size].to_vec()));
+    }
+    Ok(segments)
+}
+
+/// ELF程序头类型：可加载段
+const PT_LOAD: u32 = 1;
+
+/// 解析ELF（32/64位，大小端均可）中的可加载段，返回(物理地址, 文件中的数据)
+///
+/// 只取段在文件中的部分（`p_filesz`），`p_memsz`多出的部分是运行时清零的.bss，不在Flash中。
+pub(crate) fn parse_elf(bytes: &[u8]) -> Result<Vec<Segment>, FirmwareError> {
+    let parse_error = |record, reason| FirmwareError::Parse { record, reason };
+    if bytes.len() < 16 || &bytes[..4] != b"\x7FELF" {
+        return Err(parse_error(0, "not an ELF file"));
+    }
+    let is_64 = match bytes[4] {
+        1 => false,
+        2 => true,
+        _ => return Err(parse_error(0, "bad ELF class")),
+    };
+    let big_endian = match bytes[5] {
+        1 => false,
+        2 => true,
+        _ => return Err(parse_error(0, "bad ELF data encoding")),
+    };
+    // 按文件字节序读取定长整数，越界（含地址溢出）时报错
+    let read = |at: usize, size: usize, record: usize| -> Result<u64, FirmwareError> {
+        let field = at
+            .checked_add(size)
+            .and_then(|end| bytes.get(at..end))
+            .ok_or(parse_error(record, "truncated ELF"))?;
+        let fold = |value: u64, &byte: &u8| value << 8 | byte as u64;
+        Ok(if big_endian {
+            field.iter().fold(0, fold)
+        } else {
+            field.iter().rev().fold(0, fold)
+        })
+    };
+    let (phoff, phentsize, phnum) = if is_64 {
+        (read(32, 8, 0)?, read(54, 2, 0)?, read(56, 2, 0)?)
+    } else {
+        (read(28, 4, 0)?, read(42, 2, 0)?, read(44, 2, 0)?)
+    };
+
+    // 程序头表项中用到的字段都在该长度之内
+    let entry_size = if is_64 { 56 } else { 32 };
+    let phoff = usize::try_from(phoff).map_err(|_| parse_error(0, "truncated ELF"))?;
+
+    let mut segments = Vec::new();
+    for index in 0..phnum as usize {
+        let record = index + 1;
+        // 先确认整个表项都在文件内，之后的字段偏移不会溢出
+        let header = index
+            .checked_mul(phentsize as usize)
+            .and_then(|skip| phoff.checked_add(skip))
+            .filter(|header| {
+                header
+                    .checked_add(entry_size)
+                    .is_some_and(|end| end <= bytes.len())
+            })
+            .ok_or(parse_error(record, "truncated ELF"))?;
+        let (kind, offset, paddr, filesz) = if is_64 {
+            (
+                read(header, 4, record)?,
+                read(header + 8, 8, record)?,
+                read(header + 24, 8, record)?,
+                read(header + 32, 8, record)?,
+            )
+        } else {
+            (
+                read(header, 4, record)?,
+                read(header + 4, 4, record)?,
+                read(header + 12, 4, record)?,
+                read(header + 16, 4, record)?,
+            )
+        };
+        if kind != PT_LOAD as u64 || filesz == 0 {
+            continue;
+        }
+        let data = offset
+            .checked_add(filesz)
+            .and_then(|end| bytes.get(usize::try_from(offset).ok()?..usize::try_from(end).ok()?))
+            .ok_or(parse_error(record, "segment data outside the file"))?;
+        segments.push((paddr, data.to_vec()));
+    }
+    Ok(segments)
+}
+
+/// 把解析出的数据编程到`flash`中（地址减去`base`即为Flash内偏移）
+///
+/// 按擦除块处理：内容有变化的字都已擦除时直接编程；否则读出整块、擦除，
+/// 再把原有内容与新数据一起写回，块内镜像未覆盖的数据保持不变。
+/// 所有操作都经过`NorFlash`接口，统计、掉电注入等照常生效。
+pub(crate) fn program<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize>(
+    flash: &mut FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>,
+    segments: &[Segment],
+    base: u32,
+) -> Result<(), FirmwareError> {
+    let capacity = ReadNorFlash::capacity(flash) as u64;
+    // 块编号 -> (块内新数据, 是否被镜像覆盖)
+    let mut blocks: BTreeMap<usize, (Vec<u8>, Vec<bool>)> = BTreeMap::new();
+    for (address, data) in segments {
+        for (index, &byte) in data.iter().enumerate() {
+            let offset = address
+                .checked_add(index as u64)
+                .and_then(|address| address.checked_sub(base as u64))
+                .filter(|&offset| offset < capacity)
+                .ok_or(FirmwareError::OutOfRange {
+                    address: address.saturating_add(index as u64),
+                })? as usize;
+            let (content, covered) = blocks
+                .entry(offset / ERASE_SIZE)
+                .or_insert_with(|| (vec![0; ERASE_SIZE], vec![false; ERASE_SIZE]));
+            content[offset % ERASE_SIZE] = byte;
+            covered[offset % ERASE_SIZE] = true;
+        }
+    }
+
+    let erased = flash.erased_value().byte();
+    for (block, (content, covered)) in blocks {
+        let start = (block * ERASE_SIZE) as u32;
+        let mut old = vec![0u8; ERASE_SIZE];
+        ReadNorFlash::read(flash, start, &mut old)?;
+        let mut merged = old.clone();
+        let mut targets = vec![false; ERASE_SIZE / WRITE_SIZE];
+        for (index, &is_covered) in covered.iter().enumerate() {
+            if is_covered {
+                merged[index] = content[index];
+                targets[index / WRITE_SIZE] = true;
+            }
+        }
+        // 内容不变的字无需编程（重复加载同一固件不会擦除）
+        for (word, target) in targets.iter_mut().enumerate() {
+            let range = word * WRITE_SIZE..(word + 1) * WRITE_SIZE;
+            *target &= merged[range.clone()] != old[range];
+        }
+        let words_erased = targets.iter().enumerate().all(|(word, &target)| {
+            !target
+                || old[word * WRITE_SIZE..(word + 1) * WRITE_SIZE]
+                    .iter()
+                    .all(|&byte| byte == erased)
+        });
+        if !words_erased {
+            // 需要擦除：整块重新编程
+            NorFlash::erase(flash, start, start + ERASE_SIZE as u32)?;
+            targets.fill(true);
+        }
+
+        // 跳过内容全为擦除值的字，相邻的字合并为一次写入
+        let mut word = 0;
+        while word < targets.len() {
+            let wanted = |word: usize| {
+                targets[word]
+                    && merged[word * WRITE_SIZE..(word + 1) * WRITE_SIZE]
+                        .iter()
+                        .any(|&byte| byte != erased)
+            };
+            if !wanted(word) {
+                word += 1;
+                continue;
+            }
+            let first = word;
+            while word < targets.len() && wanted(word) {
+                word += 1;
+            }
+            let range = first * WRITE_SIZE..word * WRITE_SIZE;
+            NorFlash::write(flash, start + range.start as u32, &merged[range])?;
+        }
+    }
+    Ok(())
+}
+
+/// 把镜像导出为指定格式（跳过内容全为擦除值的记录）
+pub(crate) fn export(
+    image: &[u8],
+    format: ImageFormat,
+    base: u32,
+    erased: u8,
+) -> Result<Vec<u8>, FirmwareError> {
+    if base as u64 + image.len() as u64 > 1 << 32 {
+        return Err(FirmwareError::OutOfRange {
+            address: base as u64 + image.len() as u64,
+        });
+    }
+    let chunk_size = match format {
+        ImageFormat::Uf2 => UF2_PAYLOAD_SIZE,
+        _ => RECORD_SIZE,
+    };
+    let chunks: Vec<(u32, &[u8])> = image
+        .chunks(chunk_size)
+        .enumerate()
+        .filter(|(_, chunk)| chunk.iter().any(|&byte| byte != erased))
+        .map(|(index, chunk)| (base + (index * chunk_size) as u32, chunk))
+        .collect();
+    Ok(match format {
+        ImageFormat::IntelHex => export_intel_hex(&chunks).into_bytes(),
+        ImageFormat::SRecord => export_srecord(&chunks).into_bytes(),
+        ImageFormat::Uf2 => export_uf2(&chunks),
+    })
+}
+
+/// 追加一条带校验和的十六进制记录
+fn push_record(text: &mut String, prefix: &str, fields: &[u8], checksum: u8) {
+    text.push_str(prefix);
+    for byte in fields {
+        let _ = write!(text, "{byte:02X}");
+    }
+    let _ = writeln!(text, "{checksum:02X}");
+}
+
+fn export_intel_hex(chunks: &[(u32, &[u8])]) -> String {
+    let mut text = String::new();
+    let mut upper = None;
+    let record = |text: &mut String, kind: u8, address: u16, data: &[u8]| {
+        let mut fields = vec![data.len() as u8];
+        fields.extend_from_slice(&address.to_be_bytes());
+        fields.push(kind);
+        fields.extend_from_slice(data);
+        let sum = fields.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        push_record(text, ":", &fields, sum.wrapping_neg());
+    };
+    for &(address, data) in chunks {
+        // 记录不跨越64KB边界，地址高16位变化时输出扩展线性地址记录
+        let mut split = vec![(address, data)];
+        let boundary = (address | 0xFFFF) as u64 + 1;
+        if address as u64 + data.len() as u64 > boundary {
+            let (head, tail) = data.split_at((boundary - address as u64) as usize);
+            split = vec![(address, head), (boundary as u32, tail)];
+        }
+        for (address, data) in split {
+            if upper != Some(address >> 16) {
+                upper = Some(address >> 16);
+                record(&mut text, 0x04, 0, &((address >> 16) as u16).to_be_bytes());
+            }
+            record(&mut text, 0x00, address as u16, data);
+        }
+    }
+    record(&mut text, 0x01, 0, &[]);
+    text
+}
+
+fn export_srecord(chunks: &[(u32, &[u8])]) -> String {
+    let mut text = String::new();
+    let record = |text: &mut String, kind: &str, address: &[u8], data: &[u8]| {
+        let mut fields = vec![(address.len() + data.len() + 1) as u8];
+        fields.extend_from_slice(address);
+        fields.extend_from_slice(data);
+        let sum = fields.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        push_record(text, kind, &fields, !sum);
+    };
+    record(&mut text, "S0", &[0, 0], b"flash-mock");
+    for &(address, data) in chunks {
+        record(&mut text, "S3", &address.to_be_bytes(), data);
+    }
+    record(&mut text, "S7", &0u32.to_be_bytes(), &[]);
+    text
+}
+
+fn export_uf2(chunks: &[(u32, &[u8])]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(chunks.len() * UF2_BLOCK_SIZE);
+    for (number, &(address, data)) in chunks.iter().enumerate() {
+        let mut block = [0u8; UF2_BLOCK_SIZE];
+        let words = [
+            UF2_MAGIC_START0,
+            UF2_MAGIC_START1,
+            0, // 标志：无family ID
+            address,
+            data.len() as u32,
+            number as u32,
+            chunks.len() as u32,
+            0,
+        ];
+        for (index, word) in words.iter().enumerate() {
+            block[index * 4..index * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        block[32..32 + data.len()].copy_from_slice(data);
+        block[UF2_BLOCK_SIZE - 4..].copy_from_slice(&UF2_MAGIC_END.to_le_bytes());
+        bytes.extend_from_slice(&block);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造只含一个程序头的小端ELF64文件，段数据紧跟在程序头之后
+    fn elf64(phoff: u64, phentsize: u16, paddr: u64, data: &[u8]) -> Vec<u8> {
+        let mut elf = vec![0u8; 64 + 56];
+        elf[..6].copy_from_slice(b"\x7FELF\x02\x01");
+        elf[32..40].copy_from_slice(&phoff.to_le_bytes());
+        elf[54..56].copy_from_slice(&phentsize.to_le_bytes());
+        elf[56..58].copy_from_slice(&1u16.to_le_bytes());
+        let header = &mut elf[64..];
+        header[..4].copy_from_slice(&PT_LOAD.to_le_bytes());
+        header[8..16].copy_from_slice(&120u64.to_le_bytes());
+        header[24..32].copy_from_slice(&paddr.to_le_bytes());
+        header[32..40].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        elf.extend_from_slice(data);
+        elf
+    }
+
+    fn is_truncated(result: Result<Vec<Segment>, FirmwareError>) -> bool {
+        matches!(
+            result,
+            Err(FirmwareError::Parse {
+                reason: "truncated ELF",
+                ..
+            })
+        )
+    }
+
+    #[test]
+    fn elf_load_segment() {
+        let segments = parse_elf(&elf64(64, 56, 0x0800_0000, &[1, 2, 3])).unwrap();
+        assert_eq!(segments, [(0x0800_0000, vec![1, 2, 3])]);
+    }
+
+    #[test]
+    fn elf_header_offsets_overflow() {
+        assert!(is_truncated(parse_elf(&elf64(u64::MAX, 56, 0, &[1]))));
+        assert!(is_truncated(parse_elf(&elf64(u64::MAX - 8, 56, 0, &[1]))));
+        assert!(is_truncated(parse_elf(&elf64(64, 56, 0, &[1])[..100])));
+    }
+
+    #[test]
+    fn elf_segment_address_overflow() {
+        let segments = parse_elf(&elf64(64, 56, u64::MAX, &[1, 2])).unwrap();
+        let mut flash = FlashMock::<1, 4, 256>::new_in_memory(1024).unwrap();
+        assert!(matches!(
+            program(&mut flash, &segments, 0),
+            Err(FirmwareError::OutOfRange { .. })
+        ));
+    }
+
+    /// 把解析出的数据按`base`放回长度为`len`、初始为0xFF的镜像
+    fn image_from(segments: &[Segment], base: u32, len: usize) -> Vec<u8> {
+        let mut image = vec![0xFF; len];
+        for (address, data) in segments {
+            let offset = (address - base as u64) as usize;
+            image[offset..offset + data.len()].copy_from_slice(data);
+        }
+        image
+    }
+
+    /// 带擦除空洞、跨越64KB边界的测试镜像
+    fn sample_image() -> Vec<u8> {
+        let mut image: Vec<u8> = (0..0x300).map(|index| (index * 7) as u8).collect();
+        image[0x40..0x80].fill(0xFF);
+        image
+    }
+
+    #[test]
+    fn export_parse_round_trip() {
+        // 基址不按记录对齐，HEX记录需要在64KB边界处拆分
+        let base = 0x0800_FFF8;
+        let image = sample_image();
+        for format in [
+            ImageFormat::IntelHex,
+            ImageFormat::SRecord,
+            ImageFormat::Uf2,
+        ] {
+            let bytes = export(&image, format, base, 0xFF).unwrap();
+            let segments = parse(&bytes, format).unwrap();
+            assert_eq!(
+                image_from(&segments, base, image.len()),
+                image,
+                "{format:?}"
+            );
+            // 全为擦除值的记录被跳过
+            assert!(
+                segments
+                    .iter()
+                    .all(|(address, _)| !(base as u64 + 0x40..base as u64 + 0x80)
+                        .contains(address)),
+                "{format:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn export_rejects_address_overflow() {
+        assert!(matches!(
+            export(&[0; 16], ImageFormat::IntelHex, u32::MAX - 7, 0xFF),
+            Err(FirmwareError::OutOfRange { .. })
+        ));
+    }
+
+    fn parse_reason(bytes: &[u8], format: ImageFormat) -> (usize, &'static str) {
+        match parse(bytes, format) {
+            Err(FirmwareError::Parse { record, reason }) => (record, reason),
+            other => panic!("expected parse error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn malformed_intel_hex() {
+        let hex = ImageFormat::IntelHex;
+        assert_eq!(parse_reason(b"0300000001020300", hex), (1, "missing ':'"));
+        assert_eq!(
+            parse_reason(b":03000000010203F6", hex),
+            (1, "checksum mismatch")
+        );
+        assert_eq!(
+            parse_reason(b":03000000010203F7\n:04000000010203F5", hex),
+            (2, "bad record length")
+        );
+        assert_eq!(
+            parse_reason(b":0000000AF6", hex),
+            (1, "unsupported record type")
+        );
+        assert_eq!(parse_reason(b":0G", hex), (1, "invalid hex digits"));
+        assert_eq!(
+            parse(b":03000000010203F7\n:00000001FF\n:0G", hex).unwrap(),
+            [(0, vec![1, 2, 3])]
+        );
+    }
+
+    #[test]
+    fn malformed_srecord() {
+        let srec = ImageFormat::SRecord;
+        assert_eq!(parse_reason(b"X1030000FC", srec), (1, "missing 'S'"));
+        assert_eq!(parse_reason(b"S1030000FD", srec), (1, "checksum mismatch"));
+        assert_eq!(parse_reason(b"S1040000FC", srec), (1, "bad record length"));
+        assert_eq!(
+            parse_reason(b"S4030000FC", srec),
+            (1, "unsupported record type")
+        );
+        assert_eq!(
+            parse(b"S1050010AABB85", srec).unwrap(),
+            [(0x0010, vec![0xAA, 0xBB])]
+        );
+    }
+
+    #[test]
+    fn malformed_uf2() {
+        let uf2 = ImageFormat::Uf2;
+        let mut bytes = export(&[0x11; 16], uf2, 0, 0xFF).unwrap();
+        assert_eq!(parse_reason(&bytes[..500], uf2), (1, "truncated UF2 block"));
+        bytes[16..20].copy_from_slice(&477u32.to_le_bytes());
+        assert_eq!(parse_reason(&bytes, uf2), (1, "bad UF2 payload size"));
+        bytes[0] ^= 1;
+        assert_eq!(parse_reason(&bytes, uf2), (1, "bad UF2 magic"));
+    }
+
+    fn flash() -> FlashMock<1, 4, 256> {
+        FlashMock::new_in_memory(1024).unwrap()
+    }
+
+    fn read(flash: &mut FlashMock<1, 4, 256>, offset: u32, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        ReadNorFlash::read(flash, offset, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn program_erased_area_without_erase() {
+        let mut flash = flash();
+        // 跨块的段，块内部分字全为擦除值时不写入
+        let segments = [(0x1000_00FE, vec![1, 2, 0xFF, 0xFF, 0xFF, 0xFF, 3, 4])];
+        program(&mut flash, &segments, 0x1000_0000).unwrap();
+        assert_eq!(
+            read(&mut flash, 0xFC, 10),
+            [0xFF, 0xFF, 1, 2, 0xFF, 0xFF, 0xFF, 0xFF, 3, 4]
+        );
+        assert_eq!(flash.stats().total_erases(), 0);
+        assert_eq!(flash.stats().bytes_written, 8);
+
+        // 重复加载同一固件不会擦除或编程
+        program(&mut flash, &segments, 0x1000_0000).unwrap();
+        assert_eq!(flash.stats().total_erases(), 0);
+        assert_eq!(flash.stats().bytes_written, 8);
+    }
+
+    #[test]
+    fn program_merges_block_when_erase_needed() {
+        let mut flash = flash();
+        NorFlash::write(&mut flash, 0, &[1; 4]).unwrap();
+        NorFlash::write(&mut flash, 100, &[9; 4]).unwrap();
+        NorFlash::write(&mut flash, 256, &[5; 4]).unwrap();
+
+        program(&mut flash, &[(1, vec![2, 2])], 0).unwrap();
+        // 块0被擦除并写回：镜像未覆盖的字节保持原样
+        assert_eq!(read(&mut flash, 0, 4), [1, 2, 2, 1]);
+        assert_eq!(read(&mut flash, 100, 4), [9; 4]);
+        assert_eq!(flash.stats().erase_counts[..2], [1, 0]);
+        assert_eq!(read(&mut flash, 256, 4), [5; 4]);
+    }
+
+    #[test]
+    fn program_rejects_out_of_range() {
+        let mut flash = flash();
+        assert!(matches!(
+            program(&mut flash, &[(0x0FFF_FFFF, vec![0])], 0x1000_0000),
+            Err(FirmwareError::OutOfRange {
+                address: 0x0FFF_FFFF
+            })
+        ));
+        assert!(matches!(
+            program(&mut flash, &[(1023, vec![0, 0])], 0),
+            Err(FirmwareError::OutOfRange { address: 1024 })
+        ));
+    }
+}
@@ -1,4 +1,3 @@
-use anyhow::{Result, anyhow};
 use embedded_storage::{
     ReadStorage, Storage,
     nor_flash::{
@@ -7,12 +6,59 @@ use embedded_storage::{
     },
 };
 use std::{
+    borrow::Cow,
+    collections::BTreeMap,
     fs::File,
-    io::{Read, Seek, SeekFrom, Write},
+    io::{Seek, SeekFrom, Write},
     path::Path,
+    sync::Arc,
+    time::Duration,
 };
 use thiserror::Error;
 
+#[cfg(feature = "async")]
+mod asynch;
+mod backend;
+mod clock;
+mod crash;
+mod diff;
+mod fault;
+mod firmware;
+#[cfg(unix)]
+mod mmap;
+mod multiwrite;
+mod options;
+mod retention;
+mod snapshot;
+mod stats;
+mod timing;
+mod trace;
+
+#[cfg(feature = "async")]
+pub use asynch::{AsyncRmwMultiwriteNorFlashStorage, AsyncRmwNorFlashStorage};
+pub use backend::{FileBackend, FlashBackend, MemoryBackend};
+pub use clock::VirtualClock;
+pub use crash::{CrashExploration, CrashExplorationError, explore_power_cuts};
+pub use diff::{BlockChange, BlockDiff, FlashDiff};
+use fault::FlipRng;
+pub use fault::{BitFlipMode, BitFlips};
+pub use firmware::{FirmwareError, ImageFormat};
+#[cfg(unix)]
+pub use mmap::MmapBackend;
+pub use multiwrite::MultiwriteFlashMock;
+pub use options::{ErasedValue, FlashOptions, Geometry, SizePolicy};
+use retention::BlockRetention;
+pub use retention::Retention;
+use snapshot::CowBlocks;
+pub use snapshot::FlashSnapshot;
+pub use stats::FlashStats;
+use stats::blocks_in;
+pub use timing::TimingProfile;
+use trace::Tracer;
+pub use trace::{
+    Journal, JournalError, ReplayError, TraceData, TraceDetail, TraceEntry, TraceOp, TraceResult,
+};
+
 // ------------------------------
 // 1. 错误类型定义（实现NorFlashError）
 // ------------------------------
@@ -24,6 +70,20 @@ pub enum FlashMockError {
     WriteToNonErased { offset: u32 },
     #[error("NOR flash check failed: {0:?}")]
     CheckFailed(NorFlashErrorKind),
+    #[error("Power lost during operation (offset: {offset})")]
+    PowerLoss { offset: u32 },
+    #[error("Erase block worn out (offset: {offset})")]
+    WornOut { offset: u32 },
+    #[error("Word programmed too many times since last erase (offset: {offset})")]
+    ProgramLimitExceeded { offset: u32 },
+    #[error("ECC word already programmed (offset: {offset})")]
+    WordAlreadyProgrammed { offset: u32 },
+    #[error("ECC error reading interrupted word (offset: {offset})")]
+    EccError { offset: u32 },
+    #[error("Image size mismatch: image has {actual} bytes, flash capacity is {expected}")]
+    ImageSizeMismatch { actual: usize, expected: usize },
+    #[error("Snapshot geometry {snapshot:?} does not match flash geometry {flash:?}")]
+    SnapshotMismatch { snapshot: Geometry, flash: Geometry },
 }
 
 impl NorFlashError for FlashMockError {
@@ -32,91 +92,1222 @@ impl NorFlashError for FlashMockError {
             FlashMockError::Io(_) => NorFlashErrorKind::Other,
             FlashMockError::WriteToNonErased { .. } => NorFlashErrorKind::Other,
             FlashMockError::CheckFailed(kind) => *kind,
+            FlashMockError::PowerLoss { .. } => NorFlashErrorKind::Other,
+            FlashMockError::WornOut { .. } => NorFlashErrorKind::Other,
+            FlashMockError::ProgramLimitExceeded { .. } => NorFlashErrorKind::Other,
+            FlashMockError::WordAlreadyProgrammed { .. } => NorFlashErrorKind::Other,
+            FlashMockError::EccError { .. } => NorFlashErrorKind::Other,
+            FlashMockError::ImageSizeMismatch { .. } => NorFlashErrorKind::Other,
+            FlashMockError::SnapshotMismatch { .. } => NorFlashErrorKind::Other,
         }
     }
 }
 
+/// 创建/打开`FlashMock`时的配置错误
+#[derive(Debug, Error)]
+pub enum FlashMockConfigError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Total capacity must be multiple of ERASE_SIZE ({capacity} % {erase_size} != 0)")]
+    CapacityNotMultiple { capacity: usize, erase_size: usize },
+    #[error("Invalid path: cannot convert to string")]
+    InvalidPath,
+    #[error("Image size mismatch: file has {actual} bytes, expected {expected} ({policy:?})")]
+    SizeMismatch {
+        actual: u64,
+        expected: u64,
+        policy: SizePolicy,
+    },
+    #[error("Geometry mismatch: image has {stored:?}, opened as {requested:?}")]
+    GeometryMismatch {
+        stored: Geometry,
+        requested: Geometry,
+    },
+    #[error("Invalid geometry file {path}: missing or malformed `{field}`")]
+    InvalidGeometryFile { path: String, field: &'static str },
+    #[error("MultiwriteNorFlash requires flash that erases to 0xFF, got {erased_value:?}")]
+    UnsupportedErasedValue { erased_value: ErasedValue },
+}
+
+/// 掉电注入配置（在write/erase过程中模拟突然断电）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerCut {
+    /// 累计编程/擦除N字节后掉电（跨多次操作累计），中断处的字节只编程一半的位
+    AfterBytes(usize),
+    /// 前N次write/erase完整执行，第N+1次执行到一半时掉电
+    AfterOps(usize),
+}
+
+/// 擦除块超过寿命后的失效方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WearOut {
+    /// 擦除直接失败，返回`FlashMockError::WornOut`
+    EraseFails,
+    /// 擦除照常完成，但每次超寿命擦除都会在块内新增一个卡死为0的位，
+    /// 之后读取会看到该位为0，向其写入会因区域未擦除而失败
+    StuckBits,
+}
+
+/// 擦写寿命配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Endurance {
+    /// 每个擦除块的额定擦除次数
+    pub cycles: u32,
+    /// 超过额定次数后的失效方式
+    pub wear_out: WearOut,
+}
+
+/// 编程（write）语义
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgramMode {
+    /// 目标区域必须已擦除，否则返回`FlashMockError::WriteToNonErased`
+    #[default]
+    EraseBeforeWrite,
+    /// 允许重复编程，结果为旧数据与新数据按位与（只能把1改为0；擦除值为0x00时为按位或）；
+    /// `max_programs`限制两次擦除之间每个WRITE_SIZE字最多编程几次，超出返回
+    /// `FlashMockError::ProgramLimitExceeded`
+    BitwiseAnd { max_programs: Option<u32> },
+    /// 带ECC的Flash（如STM32L4/G4/H7内部Flash）：每个WRITE_SIZE字在两次擦除之间只能编程一次，
+    /// 即使只是把1改为0也不行（返回`FlashMockError::WordAlreadyProgrammed`）；
+    /// 编程被掉电打断的字在读取时返回`FlashMockError::EccError`
+    Ecc,
+}
+
+/// ECC模式下已编程字的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EccWord {
+    /// 编程完成
+    Programmed,
+    /// 编程被掉电打断，ECC校验失败
+    Torn,
+}
+
 // ------------------------------
 // 2. FlashMock结构体（用const泛型定义静态参数）
 // ------------------------------
 /// const泛型说明：
 /// - READ_SIZE: 最小读取单位（编译时确定，需是2的幂）
-/// - WRITE_SIZE: 最小写入单位（编译时确定，需是2的幂）
-/// - ERASE_SIZE: 最小擦除单位（编译时确定，需是2的幂）
+/// - WRITE_SIZE: 最小写入单位（编译时确定，需是2的幂，且是READ_SIZE的整数倍）
+/// - ERASE_SIZE: 最小擦除单位（编译时确定，需是2的幂，且是WRITE_SIZE的整数倍）
 pub struct FlashMock<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> {
-    _path: String,         // 持久化文件路径
-    total_capacity: usize, // 总存储容量（需是ERASE_SIZE的整数倍）
-    file: File,            // 文件句柄
+    _path: Option<String>,                // 持久化文件路径（纯内存模式为None）
+    total_capacity: usize,                // 总存储容量（需是ERASE_SIZE的整数倍）
+    backend: Box<dyn FlashBackend>,       // 后端存储（文件、内存或用户自定义）
+    power_cut: Option<PowerCut>,          // 已设置的掉电注入点（触发后自动清除）
+    cut_ops: usize,                       // 设置掉电注入后已执行的write/erase次数
+    cut_bytes: usize,                     // 设置掉电注入后已编程/擦除的字节数
+    powered_off: bool,                    // 是否处于掉电状态（需power_cycle或重新打开）
+    stats: FlashStats,                    // 擦写次数与读写字节统计
+    endurance: Option<Endurance>,         // 擦写寿命配置
+    erase_cycles: Vec<u32>,               // 每个擦除块累计的擦除次数（不受reset_stats影响）
+    stuck_bits: BTreeMap<u32, u8>,        // 卡死在编程态的位（地址 -> 位掩码）
+    program_mode: ProgramMode,            // 编程语义（先擦后写或按位与）
+    word_programs: BTreeMap<u32, u32>,    // 上次擦除后每个字的编程次数（仅在限制编程次数时记录）
+    ecc_words: BTreeMap<u32, EccWord>,    // ECC模式下上次擦除后已编程的字
+    erased_value: ErasedValue,            // 擦除后的字节值（决定编程方向）
+    bit_flips: Option<BitFlips>,          // 位翻转故障模型
+    flip_rng: FlipRng,                    // 位翻转使用的伪随机数发生器
+    clock: VirtualClock,                  // 模拟时钟（每个操作按耗时模型推进）
+    virtual_time: bool,                   // 是否只推进模拟时钟而不真正等待
+    retention: Option<Retention>,         // 数据保持衰减模型
+    block_retention: Vec<BlockRetention>, // 每个擦除块上次编程的时间与已施加的衰减
+    timing: Option<TimingProfile>,        // 操作耗时模型
+    pending_busy: Duration,               // 本次操作尚未等待的耗时
+    tracer: Option<Tracer>,               // 操作跟踪记录器
+    cow: CowBlocks,                       // 快照的写时复制状态
 }
 
 impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize>
     FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
 {
     /// 创建模拟NOR Flash实例
+    /// - `path`: 持久化文件路径（已存在时长度必须等于`total_capacity`）
+    /// - `total_capacity`: 总存储容量（必须是ERASE_SIZE的整数倍）
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        total_capacity: usize,
+    ) -> Result<Self, FlashMockConfigError> {
+        Self::open(path, total_capacity, FlashOptions::new())
+    }
+
+    /// 创建以内存映射文件为后端的模拟NOR Flash实例，适合大容量镜像
+    /// - `path`: 持久化文件路径（规则同`new`）
+    /// - `total_capacity`: 总存储容量（必须是ERASE_SIZE的整数倍）
+    #[cfg(unix)]
+    pub fn new_mmap<P: AsRef<Path>>(
+        path: P,
+        total_capacity: usize,
+    ) -> Result<Self, FlashMockConfigError> {
+        Self::open(path, total_capacity, FlashOptions::new().mmap(true))
+    }
+
+    /// 按给定选项创建模拟NOR Flash实例
     /// - `path`: 持久化文件路径
     /// - `total_capacity`: 总存储容量（必须是ERASE_SIZE的整数倍）
-    pub fn new<P: AsRef<Path>>(path: P, total_capacity: usize) -> Result<Self> {
-        // 编译时验证核心参数（2的幂 + 容量倍数）
-        if (READ_SIZE & (READ_SIZE - 1)) != 0 {
-            return Err(anyhow!(
-                "READ_SIZE must be a power of two (got {READ_SIZE})"
-            ));
-        }
-        if (WRITE_SIZE & (WRITE_SIZE - 1)) != 0 {
-            return Err(anyhow!(
-                "WRITE_SIZE must be a power of two (got {WRITE_SIZE})"
-            ));
-        }
-        if (ERASE_SIZE & (ERASE_SIZE - 1)) != 0 {
-            return Err(anyhow!(
-                "ERASE_SIZE must be a power of two (got {ERASE_SIZE})"
-            ));
-        }
-        if total_capacity % ERASE_SIZE != 0 {
-            return Err(anyhow!(
-                "Total capacity must be multiple of ERASE_SIZE ({} % {} != 0)",
+    /// - `options`: 文件长度不一致的处理策略、几何信息校验、后端类型、擦除值
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        total_capacity: usize,
+        options: FlashOptions,
+    ) -> Result<Self, FlashMockConfigError> {
+        Self::check_geometry(total_capacity)?;
+        let (path, file) = Self::open_image_file(path, total_capacity, &options)?;
+        #[cfg(unix)]
+        if options.mmap {
+            return Ok(Self::from_backend(
+                Some(path),
+                Box::new(MmapBackend::new(file, total_capacity)?),
                 total_capacity,
-                ERASE_SIZE
+                options.erased_value,
             ));
         }
+        Ok(Self::from_backend(
+            Some(path),
+            Box::new(FileBackend::new(file)),
+            total_capacity,
+            options.erased_value,
+        ))
+    }
 
+    /// 打开镜像文件（不存在则创建并填充擦除值），并按选项校验长度与几何参数
+    fn open_image_file<P: AsRef<Path>>(
+        path: P,
+        total_capacity: usize,
+        options: &FlashOptions,
+    ) -> Result<(String, File), FlashMockConfigError> {
         // 处理文件路径
         let path = path
             .as_ref()
             .to_str()
-            .ok_or_else(|| anyhow!("Invalid path: cannot convert to string"))?
+            .ok_or(FlashMockConfigError::InvalidPath)?
             .to_string();
 
-        // 初始化文件（不存在则创建并填充0xFF）
-        let file = if !Path::new(&path).exists() {
-            let mut file = File::create(&path)?;
-            let erase_block = vec![0xFFu8; ERASE_SIZE];
+        // 初始化文件（不存在则创建并填充擦除值）
+        let created = !Path::new(&path).exists();
+        let file = if created {
+            let mut file = File::options()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+            let erase_block = vec![options.erased_value.byte(); ERASE_SIZE];
             for _ in 0..(total_capacity / ERASE_SIZE) {
                 file.write_all(&erase_block)?;
             }
             file
         } else {
-            File::options().read(true).write(true).open(&path)?
+            let file = File::options().read(true).write(true).open(&path)?;
+            Self::fit_image_file(&file, total_capacity, options)?;
+            file
         };
 
-        Ok(Self {
+        // 记录/校验几何参数
+        if options.geometry_sidecar {
+            let geometry = Geometry {
+                read_size: READ_SIZE,
+                write_size: WRITE_SIZE,
+                erase_size: ERASE_SIZE,
+                capacity: total_capacity,
+            };
+            if !created && let Some(stored) = Geometry::load(&path)? {
+                // 容量变化已由size_policy处理过，其余参数必须完全一致
+                let same_layout = Geometry {
+                    capacity: total_capacity,
+                    ..stored
+                } == geometry;
+                let capacity_ok =
+                    stored.capacity == total_capacity || options.size_policy != SizePolicy::Reject;
+                if !same_layout || !capacity_ok {
+                    return Err(FlashMockConfigError::GeometryMismatch {
+                        stored,
+                        requested: geometry,
+                    });
+                }
+            }
+            geometry.store(&path)?;
+        }
+        Ok((path, file))
+    }
+
+    /// 校验已有镜像文件的长度，按策略补齐或截断
+    fn fit_image_file(
+        file: &File,
+        total_capacity: usize,
+        options: &FlashOptions,
+    ) -> Result<(), FlashMockConfigError> {
+        let policy = options.size_policy;
+        let actual = file.metadata()?.len();
+        let expected = total_capacity as u64;
+        match (actual.cmp(&expected), policy) {
+            (std::cmp::Ordering::Equal, _) => {}
+            (std::cmp::Ordering::Less, SizePolicy::Grow) => {
+                let mut file = file;
+                file.seek(SeekFrom::End(0))?;
+                let erase_block = vec![options.erased_value.byte(); ERASE_SIZE];
+                let mut remaining = (expected - actual) as usize;
+                while remaining > 0 {
+                    let step = remaining.min(ERASE_SIZE);
+                    file.write_all(&erase_block[..step])?;
+                    remaining -= step;
+                }
+            }
+            (std::cmp::Ordering::Greater, SizePolicy::Truncate) => file.set_len(expected)?,
+            _ => {
+                return Err(FlashMockConfigError::SizeMismatch {
+                    actual,
+                    expected,
+                    policy,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// 创建纯内存的模拟NOR Flash实例（初始全为0xFF，不读写任何文件）
+    /// - `total_capacity`: 总存储容量（必须是ERASE_SIZE的整数倍）
+    pub fn new_in_memory(total_capacity: usize) -> Result<Self, FlashMockConfigError> {
+        Self::new_in_memory_with(total_capacity, FlashOptions::new())
+    }
+
+    /// 按给定选项创建纯内存的模拟NOR Flash实例（初始全为擦除值）
+    /// - `total_capacity`: 总存储容量（必须是ERASE_SIZE的整数倍）
+    /// - `options`: 只使用其中的擦除值，文件相关选项被忽略
+    pub fn new_in_memory_with(
+        total_capacity: usize,
+        options: FlashOptions,
+    ) -> Result<Self, FlashMockConfigError> {
+        Self::check_geometry(total_capacity)?;
+        let erased_value = options.erased_value;
+        Ok(Self::from_backend(
+            None,
+            Box::new(MemoryBackend::new(vec![
+                erased_value.byte();
+                total_capacity
+            ])),
+            total_capacity,
+            erased_value,
+        ))
+    }
+
+    /// 在用户提供的后端上创建模拟NOR Flash实例（后端内容保持原样，不做初始化，擦除值为0xFF）
+    /// - `backend`: 后端存储，至少能容纳`total_capacity`字节
+    /// - `total_capacity`: 总存储容量（必须是ERASE_SIZE的整数倍）
+    pub fn with_backend<B: FlashBackend + 'static>(
+        backend: B,
+        total_capacity: usize,
+    ) -> Result<Self, FlashMockConfigError> {
+        Self::with_backend_options(backend, total_capacity, FlashOptions::new())
+    }
+
+    /// 按给定选项在用户提供的后端上创建模拟NOR Flash实例（后端内容保持原样，不做初始化）
+    /// - `backend`: 后端存储，至少能容纳`total_capacity`字节
+    /// - `total_capacity`: 总存储容量（必须是ERASE_SIZE的整数倍）
+    /// - `options`: 只使用其中的擦除值，文件相关选项被忽略
+    pub fn with_backend_options<B: FlashBackend + 'static>(
+        backend: B,
+        total_capacity: usize,
+        options: FlashOptions,
+    ) -> Result<Self, FlashMockConfigError> {
+        Self::check_geometry(total_capacity)?;
+        Ok(Self::from_backend(
+            None,
+            Box::new(backend),
+            total_capacity,
+            options.erased_value,
+        ))
+    }
+
+    /// 编译期几何参数校验（在构造函数中引用，参数非法的实例化无法通过编译）：
+    /// 三个大小均为非零的2的幂，且READ_SIZE <= WRITE_SIZE <= ERASE_SIZE、逐级整除
+    const GEOMETRY_CHECK: () = {
+        assert!(
+            READ_SIZE.is_power_of_two(),
+            "READ_SIZE must be a non-zero power of two"
+        );
+        assert!(
+            WRITE_SIZE.is_power_of_two(),
+            "WRITE_SIZE must be a non-zero power of two"
+        );
+        assert!(
+            ERASE_SIZE.is_power_of_two(),
+            "ERASE_SIZE must be a non-zero power of two"
+        );
+        assert!(
+            WRITE_SIZE.is_multiple_of(READ_SIZE),
+            "WRITE_SIZE must be a multiple of READ_SIZE"
+        );
+        assert!(
+            ERASE_SIZE.is_multiple_of(WRITE_SIZE),
+            "ERASE_SIZE must be a multiple of WRITE_SIZE"
+        );
+    };
+
+    /// 校验核心参数
+    fn check_geometry(total_capacity: usize) -> Result<(), FlashMockConfigError> {
+        // 触发编译期几何参数校验，运行时只需检查容量
+        let () = Self::GEOMETRY_CHECK;
+        if !total_capacity.is_multiple_of(ERASE_SIZE) {
+            return Err(FlashMockConfigError::CapacityNotMultiple {
+                capacity: total_capacity,
+                erase_size: ERASE_SIZE,
+            });
+        }
+        Ok(())
+    }
+
+    fn from_backend(
+        path: Option<String>,
+        backend: Box<dyn FlashBackend>,
+        total_capacity: usize,
+        erased_value: ErasedValue,
+    ) -> Self {
+        Self {
             _path: path,
             total_capacity,
-            file,
+            backend,
+            power_cut: None,
+            cut_ops: 0,
+            cut_bytes: 0,
+            powered_off: false,
+            stats: FlashStats::new(total_capacity / ERASE_SIZE),
+            endurance: None,
+            erase_cycles: vec![0; total_capacity / ERASE_SIZE],
+            stuck_bits: BTreeMap::new(),
+            program_mode: ProgramMode::EraseBeforeWrite,
+            word_programs: BTreeMap::new(),
+            ecc_words: BTreeMap::new(),
+            erased_value,
+            bit_flips: None,
+            flip_rng: FlipRng::new(0),
+            clock: VirtualClock::new(),
+            virtual_time: false,
+            retention: None,
+            block_retention: vec![BlockRetention::default(); total_capacity / ERASE_SIZE],
+            timing: None,
+            pending_busy: Duration::ZERO,
+            tracer: None,
+            cow: CowBlocks::default(),
+        }
+    }
+
+    /// 整个Flash内容的零拷贝只读视图（模拟XIP直接读取），后端不支持时返回`None`
+    ///
+    /// 视图直接反映后端数据，不经过掉电状态检查，也不计入读取统计。
+    pub fn as_slice(&self) -> Option<&[u8]> {
+        self.backend.as_slice()
+    }
+
+    /// 设置掉电注入点（`None`为关闭），计数从调用时开始
+    pub fn set_power_cut(&mut self, cut: Option<PowerCut>) {
+        self.power_cut = cut;
+        self.cut_ops = 0;
+        self.cut_bytes = 0;
+    }
+
+    /// 是否已因掉电注入而断电
+    pub fn is_powered_off(&self) -> bool {
+        self.powered_off
+    }
+
+    /// 模拟重新上电：清除掉电状态，文件中残留的撕裂数据保持不变
+    /// （效果等同于用`FlashMock::new`重新打开同一文件）
+    pub fn power_cycle(&mut self) {
+        self.powered_off = false;
+        self.record_trace(TraceOp::PowerCycle, 0, 0, TraceData::None, &Ok(()));
+    }
+
+    /// 当前的使用统计（每块擦写次数、累计读写擦字节数）
+    pub fn stats(&self) -> &FlashStats {
+        &self.stats
+    }
+
+    /// 清零使用统计
+    pub fn reset_stats(&mut self) {
+        self.stats = FlashStats::new(self.total_capacity / ERASE_SIZE);
+    }
+
+    /// 设置擦写寿命（`None`为无限寿命）
+    ///
+    /// 寿命按本实例创建以来每个块的擦除次数计算，不写入镜像文件。
+    pub fn set_endurance(&mut self, endurance: Option<Endurance>) {
+        self.endurance = endurance;
+    }
+
+    /// 每个擦除块累计的擦除次数（用于寿命判断，不受`reset_stats`影响）
+    pub fn erase_cycles(&self) -> &[u32] {
+        &self.erase_cycles
+    }
+
+    /// 设置位翻转故障模型（`None`为关闭），并用其种子重置伪随机数发生器
+    ///
+    /// `BitFlipMode::Persistent`模式下`targets`中的位立即在存储中翻转。
+    /// `targets`中有地址超出容量时返回`CheckFailed(OutOfBounds)`，存储与原有配置保持不变。
+    pub fn set_bit_flips(&mut self, bit_flips: Option<BitFlips>) -> Result<(), FlashMockError> {
+        if let Some(faults) = &bit_flips {
+            // 先校验全部目标地址，避免部分翻转已写入存储后才报错
+            if faults
+                .targets
+                .iter()
+                .any(|&(address, _)| address as usize >= self.total_capacity)
+            {
+                return Err(FlashMockError::CheckFailed(NorFlashErrorKind::OutOfBounds));
+            }
+            self.flip_rng = FlipRng::new(faults.seed);
+            if faults.mode == BitFlipMode::Persistent {
+                for &(address, mask) in &faults.targets {
+                    let mut byte = [0u8];
+                    self.backend.read_at(address, &mut byte)?;
+                    self.store(address, &[byte[0] ^ mask])?;
+                }
+            }
+        }
+        self.bit_flips = bit_flips;
+        Ok(())
+    }
+
+    /// 对读出的数据施加位翻转；读干扰模式下把随机翻转写回存储
+    fn inject_bit_flips(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), FlashMockError> {
+        let Some(faults) = &self.bit_flips else {
+            return Ok(());
+        };
+        let flipped = self.flip_rng.flip(bytes, faults.bit_error_rate);
+        match faults.mode {
+            BitFlipMode::Transient => {
+                let end = offset as u64 + bytes.len() as u64;
+                for &(address, mask) in &faults.targets {
+                    if address >= offset && (address as u64) < end {
+                        bytes[(address - offset) as usize] ^= mask;
+                    }
+                }
+            }
+            BitFlipMode::Persistent => {
+                if flipped {
+                    self.store(offset, bytes)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 设置数据保持衰减模型（`None`为数据永久保持）
+    pub fn set_retention(&mut self, retention: Option<Retention>) {
+        self.retention = retention;
+    }
+
+    /// 推进模拟时钟
+    pub fn advance_time(&mut self, duration: Duration) {
+        self.clock.advance(duration);
+    }
+
+    /// 模拟时钟的当前时间
+    pub fn elapsed_time(&self) -> Duration {
+        self.clock.now()
+    }
+
+    /// 切换到虚拟时间模式：`Some(clock)`时改用该时钟，操作只推进时钟而不`sleep`/等待定时器；
+    /// `None`时恢复真实等待（保留当前时钟）
+    pub fn set_virtual_clock(&mut self, clock: Option<VirtualClock>) {
+        self.virtual_time = clock.is_some();
+        if let Some(clock) = clock {
+            self.clock = clock;
+        }
+    }
+
+    /// 模拟时钟的共享句柄
+    pub fn clock(&self) -> VirtualClock {
+        self.clock.clone()
+    }
+
+    /// 对范围内的块施加到当前时间为止尚未施加的衰减
+    fn apply_retention(&mut self, offset: u32, length: usize) -> Result<(), FlashMockError> {
+        let Some(retention) = self.retention else {
+            return Ok(());
+        };
+        let erased = self.erased_value.byte();
+        for block in blocks_in(offset, length, ERASE_SIZE) {
+            let cycles = self.erase_cycles[block];
+            if cycles < retention.min_cycles {
+                continue;
+            }
+            let state = self.block_retention[block];
+            let periods = retention.periods(state.since, self.clock.now());
+            if periods <= state.applied {
+                continue;
+            }
+            // 由块编号、擦除次数和衰减序号确定性地挑选漂移的位
+            let base = mix64(((block as u64) << 32) | cycles as u64);
+            let flips = (periods - state.applied) * retention.bits_per_period as u64;
+            let first = state.applied * retention.bits_per_period as u64;
+            for index in first..first + flips {
+                let hash = mix64(base ^ index);
+                let address = (block * ERASE_SIZE) as u32 + (hash % ERASE_SIZE as u64) as u32;
+                let mask = 1u8 << ((hash >> 32) % 8);
+                let mut byte = [0u8];
+                self.backend.read_at(address, &mut byte)?;
+                self.store(address, &[(byte[0] & !mask) | (erased & mask)])?;
+            }
+            self.block_retention[block].applied = periods;
+        }
+        Ok(())
+    }
+
+    /// 擦除后重新开始计算范围内各块的保持时间
+    ///
+    /// 编程不重新计时：否则向块内追加写入会抹掉块内已有数据已经过的衰减时间。
+    fn restart_retention(&mut self, offset: u32, length: usize) {
+        for block in blocks_in(offset, length, ERASE_SIZE) {
+            self.block_retention[block] = BlockRetention {
+                since: self.clock.now(),
+                applied: 0,
+            };
+        }
+    }
+
+    /// 设置操作耗时模型（`None`为所有操作立即完成）
+    ///
+    /// 阻塞版操作在返回前`sleep`对应时长，异步版则等待一个定时器
+    /// （虚拟时间模式下只推进时钟，见`set_virtual_clock`）；
+    /// 累计耗时记入`FlashStats::busy_time`。
+    pub fn set_timing(&mut self, timing: Option<TimingProfile>) {
+        self.timing = timing;
+    }
+
+    /// 记录一次操作的耗时并推进模拟时钟，虚拟时间模式下不再等待
+    fn charge_busy(&mut self, duration: Duration) {
+        self.stats.record_busy(duration);
+        self.clock.advance(duration);
+        if !self.virtual_time {
+            self.pending_busy += duration;
+        }
+    }
+
+    /// 取出尚未等待的耗时
+    pub(crate) fn take_pending_busy(&mut self) -> Duration {
+        std::mem::take(&mut self.pending_busy)
+    }
+
+    /// 阻塞等待尚未等待的耗时
+    fn wait_busy(&mut self) {
+        let duration = self.take_pending_busy();
+        if !duration.is_zero() {
+            std::thread::sleep(duration);
+        }
+    }
+
+    /// 开始跟踪每次`read`/`write`/`erase`/`power_cycle`（丢弃之前的跟踪记录）
+    /// - `detail`: 保存数据哈希还是完整数据（重放需要`TraceDetail::Full`）
+    /// - `journal`: 同时追加写入的磁盘日志路径（可用`Journal::load`读回并重放）
+    pub fn start_trace<P: AsRef<Path>>(
+        &mut self,
+        detail: TraceDetail,
+        journal: Option<P>,
+    ) -> Result<(), JournalError> {
+        let journal = journal.as_ref().map(AsRef::as_ref);
+        self.tracer = Some(Tracer::new(detail, journal, self.geometry())?);
+        Ok(())
+    }
+
+    /// 当前的跟踪记录（未开始跟踪时为空）
+    pub fn trace(&self) -> &[TraceEntry] {
+        self.tracer
+            .as_ref()
+            .map_or(&[], |tracer| tracer.entries.as_slice())
+    }
+
+    /// 停止跟踪并取回跟踪记录（磁盘日志写入失败时返回错误）
+    pub fn stop_trace(&mut self) -> Result<Vec<TraceEntry>, JournalError> {
+        match self.tracer.take() {
+            Some(tracer) => tracer.finish(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// 追加一条跟踪记录
+    fn record_trace(
+        &mut self,
+        op: TraceOp,
+        offset: u32,
+        length: usize,
+        data: TraceData,
+        result: &Result<(), FlashMockError>,
+    ) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(TraceEntry {
+                op,
+                offset,
+                length: length as u32,
+                data,
+                result: result.into(),
+            });
+        }
+    }
+
+    /// 按跟踪详细程度整理数据（未开始跟踪时不复制）
+    fn trace_data(&self, bytes: &[u8]) -> TraceData {
+        self.tracer
+            .as_ref()
+            .map_or(TraceData::None, |tracer| tracer.data(bytes))
+    }
+
+    /// 保存当前内容与磨损计数的快照
+    ///
+    /// 自上次快照/恢复以来未修改的块直接共用上次的数据，只有修改过的块才重新读取。
+    pub fn snapshot(&mut self) -> Result<FlashSnapshot, FlashMockError> {
+        let mut blocks = Vec::with_capacity(self.total_capacity / ERASE_SIZE);
+        for block in 0..self.total_capacity / ERASE_SIZE {
+            match &self.cow.base {
+                Some(base) if !self.cow.dirty[block] => blocks.push(Arc::clone(&base[block])),
+                _ => {
+                    let mut data = vec![0u8; ERASE_SIZE];
+                    self.backend
+                        .read_at((block * ERASE_SIZE) as u32, &mut data)?;
+                    blocks.push(Arc::from(data));
+                }
+            }
+        }
+        self.cow = CowBlocks {
+            base: Some(blocks.clone()),
+            dirty: vec![false; blocks.len()],
+        };
+        Ok(FlashSnapshot {
+            geometry: self.geometry(),
+            blocks,
+            erase_cycles: self.erase_cycles.clone(),
+            stuck_bits: self.stuck_bits.clone(),
+            stats: self.stats.clone(),
+            word_programs: self.word_programs.clone(),
+            ecc_words: self.ecc_words.clone(),
+            block_retention: self.block_retention.clone(),
         })
     }
 
-    /// 检查目标区域是否已擦除（全为0xFF）
+    /// 恢复到快照时的内容与磨损计数（掉电状态、模式等配置保持不变）
+    ///
+    /// 只写回与当前内容不同的块；快照必须来自几何参数相同的实例，
+    /// 否则返回`FlashMockError::SnapshotMismatch`。
+    pub fn restore(&mut self, snapshot: &FlashSnapshot) -> Result<(), FlashMockError> {
+        self.check_snapshot(snapshot)?;
+        for (block, data) in snapshot.blocks.iter().enumerate() {
+            let unchanged = matches!(&self.cow.base, Some(base)
+                if !self.cow.dirty[block] && Arc::ptr_eq(&base[block], data));
+            if !unchanged {
+                self.backend.write_at((block * ERASE_SIZE) as u32, data)?;
+            }
+        }
+        self.cow = CowBlocks {
+            base: Some(snapshot.blocks.clone()),
+            dirty: vec![false; snapshot.blocks.len()],
+        };
+        self.erase_cycles.clone_from(&snapshot.erase_cycles);
+        self.stuck_bits.clone_from(&snapshot.stuck_bits);
+        self.stats.clone_from(&snapshot.stats);
+        self.word_programs.clone_from(&snapshot.word_programs);
+        self.ecc_words.clone_from(&snapshot.ecc_words);
+        self.block_retention.clone_from(&snapshot.block_retention);
+        Ok(())
+    }
+
+    /// 检查快照的几何参数（块数与块大小）与本实例一致
+    fn check_snapshot(&self, snapshot: &FlashSnapshot) -> Result<(), FlashMockError> {
+        let flash = self.geometry();
+        let blocks_match = snapshot.blocks.len() == self.total_capacity / ERASE_SIZE
+            && snapshot
+                .blocks
+                .iter()
+                .all(|block| block.len() == ERASE_SIZE);
+        if snapshot.geometry != flash || !blocks_match {
+            return Err(FlashMockError::SnapshotMismatch {
+                snapshot: snapshot.geometry,
+                flash,
+            });
+        }
+        Ok(())
+    }
+
+    /// 按本实例的几何参数与擦除值比较两个镜像
+    ///
+    /// 两个镜像的长度都必须等于本实例的容量，否则返回`FlashMockError::ImageSizeMismatch`。
+    pub fn diff_images(&self, old: &[u8], new: &[u8]) -> Result<FlashDiff, FlashMockError> {
+        for image in [old, new] {
+            if image.len() != self.total_capacity {
+                return Err(FlashMockError::ImageSizeMismatch {
+                    actual: image.len(),
+                    expected: self.total_capacity,
+                });
+            }
+        }
+        Ok(FlashDiff::new(
+            old,
+            new,
+            WRITE_SIZE,
+            ERASE_SIZE,
+            self.erased_value,
+        ))
+    }
+
+    /// 比较快照与当前内容（快照为旧、当前为新）
+    pub fn diff_since(&mut self, snapshot: &FlashSnapshot) -> Result<FlashDiff, FlashMockError> {
+        self.check_snapshot(snapshot)?;
+        let current = self.read_image()?;
+        self.diff_images(&snapshot.to_image(), &current)
+    }
+
+    /// 比较两个镜像文件（长度必须等于本实例的容量）
+    pub fn diff_image_files<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        old: P,
+        new: Q,
+    ) -> Result<FlashDiff, FlashMockError> {
+        let old = std::fs::read(old)?;
+        let new = std::fs::read(new)?;
+        self.diff_images(&old, &new)
+    }
+
+    /// 写入后端并标记修改过的块
+    fn store(&mut self, offset: u32, bytes: &[u8]) -> std::io::Result<()> {
+        self.cow.mark(blocks_in(offset, bytes.len(), ERASE_SIZE));
+        self.backend.write_at(offset, bytes)
+    }
+
+    /// 填充后端并标记修改过的块
+    fn store_fill(&mut self, offset: u32, length: usize, value: u8) -> std::io::Result<()> {
+        self.cow.mark(blocks_in(offset, length, ERASE_SIZE));
+        self.backend.fill(offset, length, value)
+    }
+
+    /// 本实例的几何参数
+    pub fn geometry(&self) -> Geometry {
+        Geometry {
+            read_size: READ_SIZE,
+            write_size: WRITE_SIZE,
+            erase_size: ERASE_SIZE,
+            capacity: self.total_capacity,
+        }
+    }
+
+    /// 擦除后的字节值
+    pub fn erased_value(&self) -> ErasedValue {
+        self.erased_value
+    }
+
+    /// 设置编程语义（切换模式会清空编程次数与ECC字状态记录）
+    ///
+    /// 需要`MultiwriteNorFlash`时请使用`MultiwriteFlashMock`包装。
+    /// ECC字状态只保存在内存中：`power_cycle`后仍然保留，重新打开镜像文件后丢失
+    /// （此时已编程的字仍会因区域未擦除而拒绝再次编程）。
+    pub fn set_program_mode(&mut self, mode: ProgramMode) {
+        self.program_mode = mode;
+        self.word_programs.clear();
+        self.ecc_words.clear();
+    }
+
+    /// 当前的编程语义
+    pub fn program_mode(&self) -> ProgramMode {
+        self.program_mode
+    }
+
+    /// 按位与模式下检查并记录每个字的编程次数
+    fn count_word_programs(&mut self, offset: u32, length: usize) -> Result<(), FlashMockError> {
+        let ProgramMode::BitwiseAnd {
+            max_programs: Some(max_programs),
+        } = self.program_mode
+        else {
+            return Ok(());
+        };
+        let words = (offset..offset + length as u32).step_by(WRITE_SIZE);
+        if let Some(word) = words
+            .clone()
+            .find(|word| self.word_programs.get(word).copied().unwrap_or(0) >= max_programs)
+        {
+            return Err(FlashMockError::ProgramLimitExceeded { offset: word });
+        }
+        for word in words {
+            *self.word_programs.entry(word).or_insert(0) += 1;
+        }
+        Ok(())
+    }
+
+    /// ECC模式下拒绝再次编程已编程过的字
+    fn check_ecc_program(&self, offset: u32, length: usize) -> Result<(), FlashMockError> {
+        match self.ecc_words.range(offset..offset + length as u32).next() {
+            Some((&word, _)) => Err(FlashMockError::WordAlreadyProgrammed { offset: word }),
+            None => Ok(()),
+        }
+    }
+
+    /// ECC模式下记录编程结果：`[offset, offset+done)`内完整写入的字为已编程，
+    /// 若`done < length`则中断处所在的字为编程中断
+    fn mark_ecc_words(&mut self, offset: u32, length: usize, done: usize) {
+        if self.program_mode != ProgramMode::Ecc {
+            return;
+        }
+        let end = offset + done as u32;
+        for word in (offset..offset + length as u32).step_by(WRITE_SIZE) {
+            if word + WRITE_SIZE as u32 <= end {
+                self.ecc_words.insert(word, EccWord::Programmed);
+            } else {
+                if done < length {
+                    self.ecc_words.insert(word, EccWord::Torn);
+                }
+                break;
+            }
+        }
+    }
+
+    /// ECC模式下读取到编程中断的字时报错
+    fn check_ecc_read(&self, offset: u32, length: usize) -> Result<(), FlashMockError> {
+        if self.ecc_words.is_empty() || length == 0 {
+            return Ok(());
+        }
+        let start = offset - offset % WRITE_SIZE as u32;
+        match self
+            .ecc_words
+            .range(start..offset + length as u32)
+            .find(|(_, state)| **state == EccWord::Torn)
+        {
+            Some((&word, _)) => Err(FlashMockError::EccError { offset: word }),
+            None => Ok(()),
+        }
+    }
+
+    /// 擦除后清除范围内各字的编程记录
+    fn forget_programmed_words(&mut self, from: u32, length: usize) {
+        let end = from + length as u32;
+        self.word_programs
+            .retain(|&word, _| word < from || word >= end);
+        self.ecc_words.retain(|&word, _| word < from || word >= end);
+    }
+
+    /// 掉电状态下拒绝一切操作
+    fn ensure_powered(&self, offset: u32) -> Result<(), FlashMockError> {
+        if self.powered_off {
+            return Err(FlashMockError::PowerLoss { offset });
+        }
+        Ok(())
+    }
+
+    /// 计算本次操作在掉电前能完成的字节数（`None`表示完整执行）
+    fn take_power_budget(&mut self, length: usize) -> Option<usize> {
+        let (done_ops, done_bytes) = (self.cut_ops, self.cut_bytes);
+        self.cut_ops += 1;
+        self.cut_bytes += length;
+        let budget = match self.power_cut? {
+            PowerCut::AfterBytes(limit) => {
+                let remaining = limit.saturating_sub(done_bytes);
+                (length > remaining).then_some(remaining)
+            }
+            PowerCut::AfterOps(ops) => (done_ops >= ops).then_some(length / 2),
+        };
+        if budget.is_some() {
+            self.power_cut = None;
+            self.powered_off = true;
+        }
+        budget
+    }
+
+    /// 读出整个Flash镜像（不经过掉电/对齐检查）
+    pub(crate) fn read_image(&mut self) -> Result<Vec<u8>, FlashMockError> {
+        let mut image = vec![0u8; self.total_capacity];
+        self.backend.read_at(0, &mut image)?;
+        Ok(image)
+    }
+
+    /// 用给定镜像覆盖整个Flash（不经过掉电/擦除检查）
+    ///
+    /// 镜像整体替换后，旧内容上的字编程记录与ECC状态不再有效，一并清空。
+    pub(crate) fn write_image(&mut self, image: &[u8]) -> Result<(), FlashMockError> {
+        self.store(0, image)?;
+        self.word_programs.clear();
+        self.ecc_words.clear();
+        Ok(())
+    }
+
+    /// 把Intel HEX / S-record / UF2固件文件编程到Flash中
+    /// - `path`: 固件文件路径
+    /// - `format`: 文件格式（可用`ImageFormat::from_path`按扩展名推断）
+    /// - `base`: Flash在目标地址空间中的起始地址，固件地址减去`base`即为Flash内偏移
+    ///
+    /// 目标字未擦除时先擦除所在的块（块内其余数据会被保留并重新编程）。
+    pub fn load_firmware<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        format: ImageFormat,
+        base: u32,
+    ) -> Result<(), FirmwareError> {
+        let bytes = std::fs::read(path)?;
+        let segments = firmware::parse(&bytes, format)?;
+        firmware::program(self, &segments, base)
+    }
+
+    /// 把ELF文件中物理地址落在Flash窗口`[base, base + 容量)`内的`PT_LOAD`段
+    /// 编程到`paddr - base`处（窗口外的段，如RAM中的.data运行地址，被忽略）
+    ///
+    /// 擦除规则同`load_firmware`；跨越窗口边界的段返回`FirmwareError::OutOfRange`。
+    pub fn load_elf<P: AsRef<Path>>(&mut self, path: P, base: u32) -> Result<(), FirmwareError> {
+        let bytes = std::fs::read(path)?;
+        let window = base as u64..base as u64 + self.total_capacity as u64;
+        let segments: Vec<_> = firmware::parse_elf(&bytes)?
+            .into_iter()
+            .filter(|(paddr, _)| window.contains(paddr))
+            .collect();
+        firmware::program(self, &segments, base)
+    }
+
+    /// 将当前Flash内容导出为Intel HEX / S-record / UF2固件文件（全为擦除值的记录被省略）
+    /// - `base`: 导出地址 = `base` + Flash内偏移
+    pub fn dump_firmware<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        format: ImageFormat,
+        base: u32,
+    ) -> Result<(), FirmwareError> {
+        let image = self.read_image()?;
+        let bytes = firmware::export(&image, format, base, self.erased_value.byte())?;
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// 将当前Flash内容导出为镜像文件
+    pub fn dump_image<P: AsRef<Path>>(&mut self, path: P) -> Result<(), FlashMockError> {
+        let image = self.read_image()?;
+        std::fs::write(path, image)?;
+        Ok(())
+    }
+
+    /// 从镜像文件加载Flash内容（文件长度必须等于总容量）
+    pub fn load_image<P: AsRef<Path>>(&mut self, path: P) -> Result<(), FlashMockError> {
+        let image = std::fs::read(path)?;
+        if image.len() != self.total_capacity {
+            return Err(FlashMockError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "image size {} does not match capacity {}",
+                    image.len(),
+                    self.total_capacity
+                ),
+            )));
+        }
+        self.write_image(&image)
+    }
+
+    /// `EraseFails`模式下，擦除范围内有块已达寿命则拒绝擦除
+    fn check_endurance(&self, from: u32, length: usize) -> Result<(), FlashMockError> {
+        let Some(Endurance {
+            cycles,
+            wear_out: WearOut::EraseFails,
+        }) = self.endurance
+        else {
+            return Ok(());
+        };
+        match blocks_in(from, length, ERASE_SIZE).find(|&block| self.erase_cycles[block] >= cycles)
+        {
+            Some(block) => Err(FlashMockError::WornOut {
+                offset: (block * ERASE_SIZE) as u32,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// 记录擦除磨损；`StuckBits`模式下为超寿命的块新增卡死位，并重新施加范围内的卡死位
+    fn wear_erase(&mut self, from: u32, length: usize) -> Result<(), FlashMockError> {
+        for block in blocks_in(from, length, ERASE_SIZE) {
+            self.erase_cycles[block] += 1;
+            if let Some(Endurance {
+                cycles,
+                wear_out: WearOut::StuckBits,
+            }) = self.endurance
+            {
+                let cycle = self.erase_cycles[block];
+                if cycle > cycles {
+                    // 由块编号和擦除次数确定性地挑选卡死位
+                    let hash = mix64(((block as u64) << 32) | cycle as u64);
+                    let address = (block * ERASE_SIZE) as u32 + (hash % ERASE_SIZE as u64) as u32;
+                    *self.stuck_bits.entry(address).or_insert(0) |= 1 << ((hash >> 32) % 8);
+                }
+            }
+        }
+
+        let end = from + length as u32;
+        let stuck: Vec<(u32, u8)> = self
+            .stuck_bits
+            .range(from..end)
+            .map(|(&address, &mask)| (address, mask))
+            .collect();
+        for (address, mask) in stuck {
+            // 卡死位保持在编程态（与擦除值相反）
+            self.store(address, &[self.erased_value.byte() ^ mask])?;
+        }
+        Ok(())
+    }
+
+    /// 检查目标区域是否已擦除（全为擦除值）
     fn is_area_erased(&mut self, offset: u32, length: usize) -> Result<bool, FlashMockError> {
-        let mut buffer = vec![0u8; length];
-        self.file.seek(SeekFrom::Start(offset as u64))?;
-        self.file.read_exact(&mut buffer)?;
-        Ok(buffer.iter().all(|&byte| byte == 0xFF))
+        Ok(self
+            .backend
+            .is_filled(offset, length, self.erased_value.byte())?)
+    }
+}
+
+/// splitmix64混合函数，用于由确定的输入生成伪随机值
+fn mix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+// ------------------------------
+// 3. 读、擦、写的核心实现（阻塞版与异步版共用，耗时由调用方等待）
+// ------------------------------
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize>
+    FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+    /// 读取数据并记录跟踪
+    pub(crate) fn read_op(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), FlashMockError> {
+        let result = self.read_inner(offset, bytes);
+        let data = self.trace_data(bytes);
+        self.record_trace(TraceOp::Read, offset, bytes.len(), data, &result);
+        result
+    }
+
+    /// 擦除数据并记录跟踪
+    pub(crate) fn erase_op(&mut self, from: u32, to: u32) -> Result<(), FlashMockError> {
+        let result = self.erase_inner(from, to);
+        let length = to.saturating_sub(from) as usize;
+        let op = TraceOp::Erase { to };
+        self.record_trace(op, from, length, TraceData::None, &result);
+        result
+    }
+
+    /// 编程数据并记录跟踪
+    pub(crate) fn write_op(&mut self, offset: u32, bytes: &[u8]) -> Result<(), FlashMockError> {
+        let result = self.write_inner(offset, bytes);
+        let data = self.trace_data(bytes);
+        self.record_trace(TraceOp::Write, offset, bytes.len(), data, &result);
+        result
+    }
+
+    /// 读取数据（掉电检查、ECC、数据保持衰减、位翻转与耗时模型）
+    fn read_inner(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), FlashMockError> {
+        self.ensure_powered(offset)?;
+        // 复用库函数检查参数（对齐 + 边界）
+        check_read(self, offset, bytes.len()).map_err(FlashMockError::CheckFailed)?;
+        self.check_ecc_read(offset, bytes.len())?;
+        self.apply_retention(offset, bytes.len())?;
+
+        // 执行文件读取
+        self.backend.read_at(offset, bytes)?;
+        self.inject_bit_flips(offset, bytes)?;
+        if let Some(timing) = self.timing {
+            self.charge_busy(timing.read_time(bytes.len()));
+        }
+        self.stats.record_read(bytes.len());
+        Ok(())
+    }
+
+    /// 擦除数据（掉电注入、寿命与耗时模型）
+    fn erase_inner(&mut self, from: u32, to: u32) -> Result<(), FlashMockError> {
+        self.ensure_powered(from)?;
+        // 复用库函数检查参数（from<=to + 对齐 + 边界）
+        check_erase(self, from, to).map_err(FlashMockError::CheckFailed)?;
+        self.check_endurance(from, (to - from) as usize)?;
+
+        // 掉电注入：只擦除前半部分，其余保持原样
+        let mut erase_length = (to - from) as usize;
+        let torn = self.take_power_budget(erase_length);
+        if let Some(done) = torn {
+            erase_length = done;
+        }
+
+        // 填充擦除值模拟擦除
+        if erase_length > 0 {
+            self.store_fill(from, erase_length, self.erased_value.byte())?;
+            self.forget_programmed_words(from, erase_length);
+            self.wear_erase(from, erase_length)?;
+            self.restart_retention(from, erase_length);
+        }
+        if let Some(timing) = self.timing {
+            self.charge_busy(timing.erase_time(
+                from,
+                erase_length,
+                ERASE_SIZE,
+                self.total_capacity,
+            ));
+        }
+        self.stats.record_erase(from, erase_length, ERASE_SIZE);
+        match torn {
+            Some(done) => Err(FlashMockError::PowerLoss {
+                offset: from + done as u32,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// 编程数据（按编程语义合并、掉电注入与耗时模型）
+    fn write_inner(&mut self, offset: u32, bytes: &[u8]) -> Result<(), FlashMockError> {
+        self.ensure_powered(offset)?;
+        // 复用库函数检查参数（对齐 + 边界）
+        check_write(self, offset, bytes.len()).map_err(FlashMockError::CheckFailed)?;
+        self.apply_retention(offset, bytes.len())?;
+
+        // 计算编程后的数据：按位与模式下按编程方向与旧数据合并，否则目标区域必须已擦除
+        let data = if let ProgramMode::BitwiseAnd { .. } = self.program_mode {
+            self.count_word_programs(offset, bytes.len())?;
+            let mut merged = vec![0u8; bytes.len()];
+            self.backend.read_at(offset, &mut merged)?;
+            merged
+                .iter_mut()
+                .zip(bytes)
+                .for_each(|(old, &new)| *old = self.erased_value.program(*old, new));
+            Cow::Owned(merged)
+        } else {
+            if self.program_mode == ProgramMode::Ecc {
+                self.check_ecc_program(offset, bytes.len())?;
+            }
+            // 验证目标区域已擦除（NOR Flash核心约束）
+            if !self.is_area_erased(offset, bytes.len())? {
+                return Err(FlashMockError::WriteToNonErased { offset });
+            }
+            Cow::Borrowed(bytes)
+        };
+
+        // 掉电注入：只写入前半部分，中断处的字节只编程一半的位
+        if let Some(done) = self.take_power_budget(bytes.len()) {
+            self.store(offset, &data[..done])?;
+            let mut programmed = done;
+            if let Some(&byte) = bytes.get(done) {
+                let address = offset + done as u32;
+                let mut old = [0u8];
+                self.backend.read_at(address, &mut old)?;
+                // 只编程低4位，高4位保持擦除态
+                let erased = self.erased_value.byte();
+                let half = (byte & 0x0F) | (erased & 0xF0);
+                self.store(address, &[self.erased_value.program(old[0], half)])?;
+                programmed += 1;
+            }
+            self.mark_ecc_words(offset, bytes.len(), done);
+            if let Some(timing) = self.timing {
+                self.charge_busy(timing.program_time(offset, programmed, WRITE_SIZE));
+            }
+            self.stats.record_program(offset, programmed, ERASE_SIZE);
+            return Err(FlashMockError::PowerLoss {
+                offset: offset + done as u32,
+            });
+        }
+
+        // 执行文件写入
+        self.store(offset, &data)?;
+        self.mark_ecc_words(offset, bytes.len(), bytes.len());
+        if let Some(timing) = self.timing {
+            self.charge_busy(timing.program_time(offset, bytes.len(), WRITE_SIZE));
+        }
+        self.stats.record_program(offset, bytes.len(), ERASE_SIZE);
+        Ok(())
     }
 }
 
 // ------------------------------
-// 3. 实现ErrorType（关联错误类型）
+// 4. 实现ErrorType（关联错误类型）
 // ------------------------------
 impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> ErrorType
     for FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
@@ -125,7 +1316,7 @@ impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> E
 }
 
 // ------------------------------
-// 4. 实现ReadNorFlash（定义READ_SIZE关联常量）
+// 5. 实现ReadNorFlash（定义READ_SIZE关联常量）
 // ------------------------------
 impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> ReadNorFlash
     for FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
@@ -134,13 +1325,9 @@ impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> R
     const READ_SIZE: usize = READ_SIZE;
 
     fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
-        // 复用库函数检查参数（对齐 + 边界）
-        check_read(self, offset, bytes.len()).map_err(FlashMockError::CheckFailed)?;
-
-        // 执行文件读取
-        self.file.seek(SeekFrom::Start(offset as u64))?;
-        self.file.read_exact(bytes)?;
-        Ok(())
+        let result = self.read_op(offset, bytes);
+        self.wait_busy();
+        result
     }
 
     fn capacity(&self) -> usize {
@@ -149,7 +1336,7 @@ impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> R
 }
 
 // ------------------------------
-// 5. 实现NorFlash（定义WRITE_SIZE/ERASE_SIZE关联常量）
+// 6. 实现NorFlash（定义WRITE_SIZE/ERASE_SIZE关联常量）
 // ------------------------------
 impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> NorFlash
     for FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
@@ -160,38 +1347,20 @@ impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> N
     const ERASE_SIZE: usize = ERASE_SIZE;
 
     fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
-        // 复用库函数检查参数（from<=to + 对齐 + 边界）
-        check_erase(self, from, to).map_err(FlashMockError::CheckFailed)?;
-
-        // 填充0xFF模拟擦除
-        let erase_length = (to - from) as usize;
-        if erase_length > 0 {
-            self.file.seek(SeekFrom::Start(from as u64))?;
-            self.file.write_all(&vec![0xFFu8; erase_length])?;
-            self.file.flush()?;
-        }
-        Ok(())
+        let result = self.erase_op(from, to);
+        self.wait_busy();
+        result
     }
 
     fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-        // 复用库函数检查参数（对齐 + 边界）
-        check_write(self, offset, bytes.len()).map_err(FlashMockError::CheckFailed)?;
-
-        // 验证目标区域已擦除（NOR Flash核心约束）
-        if !self.is_area_erased(offset, bytes.len())? {
-            return Err(FlashMockError::WriteToNonErased { offset });
-        }
-
-        // 执行文件写入
-        self.file.seek(SeekFrom::Start(offset as u64))?;
-        self.file.write_all(bytes)?;
-        self.file.flush()?;
-        Ok(())
+        let result = self.write_op(offset, bytes);
+        self.wait_busy();
+        result
     }
 }
 
 // ------------------------------
-// 6. 实现ReadStorage（兼容上层只读接口）
+// 7. 实现ReadStorage（兼容上层只读接口）
 // ------------------------------
 impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> ReadStorage
     for FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
@@ -208,7 +1377,7 @@ impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> R
 }
 
 // ------------------------------
-// 7. 实现Storage（兼容上层读写接口，自动擦除）
+// 8. 实现Storage（兼容上层读写接口，自动擦除）
 // ------------------------------
 impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> Storage
     for FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
@@ -223,12 +1392,129 @@ impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> S
 }
 
 // ------------------------------
-// 8. Drop trait（确保数据持久化）
+// 9. Drop trait（确保数据持久化）
 // ------------------------------
 impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> Drop
     for FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
 {
     fn drop(&mut self) {
-        let _ = self.file.sync_all(); // 同步文件到磁盘
+        let _ = self.backend.sync(); // 同步文件到磁盘
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Flash = FlashMock<1, 4, 256>;
+
+    fn read(flash: &mut Flash, offset: u32, len: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; len];
+        ReadNorFlash::read(flash, offset, &mut buf).unwrap();
+        buf
+    }
+
+    fn write(flash: &mut Flash, offset: u32, bytes: &[u8]) -> Result<(), FlashMockError> {
+        NorFlash::write(flash, offset, bytes)
+    }
+
+    fn erase(flash: &mut Flash, from: u32, to: u32) -> Result<(), FlashMockError> {
+        NorFlash::erase(flash, from, to)
+    }
+
+    fn is_power_loss(result: Result<(), FlashMockError>, at: u32) -> bool {
+        matches!(result, Err(FlashMockError::PowerLoss { offset }) if offset == at)
+    }
+
+    #[test]
+    fn after_bytes_accumulates_across_operations() {
+        let mut flash = Flash::new_in_memory(1024).unwrap();
+        flash.set_power_cut(Some(PowerCut::AfterBytes(6)));
+        write(&mut flash, 0, &[0x11; 4]).unwrap();
+        // 第二次写入完成2字节后掉电，第3个字节只编程低4位
+        assert!(is_power_loss(write(&mut flash, 4, &[0x12; 4]), 6));
+        assert!(flash.is_powered_off());
+        flash.power_cycle();
+        assert_eq!(
+            read(&mut flash, 0, 8),
+            [0x11, 0x11, 0x11, 0x11, 0x12, 0x12, 0xF2, 0xFF]
+        );
+        // 掉电只注入一次
+        write(&mut flash, 8, &[0x00; 4]).unwrap();
+    }
+
+    #[test]
+    fn after_bytes_exact_limit_completes_operation() {
+        let mut flash = Flash::new_in_memory(1024).unwrap();
+        flash.set_power_cut(Some(PowerCut::AfterBytes(4)));
+        write(&mut flash, 0, &[0x00; 4]).unwrap();
+        assert!(is_power_loss(write(&mut flash, 4, &[0x34; 4]), 4));
+        flash.power_cycle();
+        assert_eq!(read(&mut flash, 4, 4), [0xF4, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn torn_byte_follows_erased_polarity() {
+        let options = FlashOptions::new().erased_value(ErasedValue::Zeros);
+        let mut flash = Flash::new_in_memory_with(1024, options).unwrap();
+        flash.set_power_cut(Some(PowerCut::AfterBytes(1)));
+        assert!(is_power_loss(write(&mut flash, 0, &[0xAB; 4]), 1));
+        flash.power_cycle();
+        // 擦除值为0x00时中断处的字节高4位保持为0
+        assert_eq!(read(&mut flash, 0, 4), [0xAB, 0x0B, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn after_ops_cuts_in_the_middle_of_the_operation() {
+        let mut flash = Flash::new_in_memory(1024).unwrap();
+        write(&mut flash, 0, &[0x00; 8]).unwrap();
+        flash.set_power_cut(Some(PowerCut::AfterOps(1)));
+        erase(&mut flash, 0, 256).unwrap();
+        assert!(is_power_loss(write(&mut flash, 0, &[0x56; 8]), 4));
+        flash.power_cycle();
+        assert_eq!(
+            read(&mut flash, 0, 8),
+            [0x56, 0x56, 0x56, 0x56, 0xF6, 0xFF, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn torn_erase_stops_at_the_budget() {
+        let mut flash = Flash::new_in_memory(1024).unwrap();
+        write(&mut flash, 0, &[0x00; 4]).unwrap();
+        write(&mut flash, 256, &[0x00; 4]).unwrap();
+        write(&mut flash, 296, &[0x00; 4]).unwrap();
+
+        // AfterOps：擦除一半范围
+        flash.set_power_cut(Some(PowerCut::AfterOps(0)));
+        assert!(is_power_loss(erase(&mut flash, 0, 512), 256));
+        flash.power_cycle();
+        assert_eq!(read(&mut flash, 0, 4), [0xFF; 4]);
+        assert_eq!(read(&mut flash, 256, 4), [0x00; 4]);
+        assert_eq!(flash.erase_cycles()[..2], [1, 0]);
+
+        // AfterBytes：擦除到预算为止，块内其余内容保持原样
+        flash.set_power_cut(Some(PowerCut::AfterBytes(40)));
+        assert!(is_power_loss(erase(&mut flash, 256, 512), 296));
+        flash.power_cycle();
+        assert_eq!(read(&mut flash, 256, 4), [0xFF; 4]);
+        assert_eq!(read(&mut flash, 296, 4), [0x00; 4]);
+    }
+
+    #[test]
+    fn powered_off_rejects_every_operation() {
+        let mut flash = Flash::new_in_memory(1024).unwrap();
+        flash.set_power_cut(Some(PowerCut::AfterOps(0)));
+        assert!(is_power_loss(write(&mut flash, 0, &[0x00; 4]), 2));
+        let mut buf = [0u8; 4];
+        assert!(is_power_loss(
+            ReadNorFlash::read(&mut flash, 8, &mut buf),
+            8
+        ));
+        assert!(is_power_loss(write(&mut flash, 12, &[0x00; 4]), 12));
+        assert!(is_power_loss(erase(&mut flash, 256, 512), 256));
+        flash.power_cycle();
+        assert_eq!(read(&mut flash, 0, 4), [0x00, 0x00, 0xF0, 0xFF]);
+        assert_eq!(flash.erase_cycles()[1], 0);
     }
 }
@@ -0,0 +1,72 @@
+use crate::FlashBackend;
+use memmap2::{MmapMut, MmapOptions};
+use std::{fs::File, io};
+
+/// 内存映射文件后端：擦除检查、擦除和读写直接在映射内存上进行，适合大容量镜像
+///
+/// 映射期间不要通过其他途径修改或截断该文件。
+pub struct MmapBackend {
+    map: MmapMut,
+}
+
+impl MmapBackend {
+    /// 以读写共享方式映射文件的前`len`字节（文件长度至少为`len`）
+    pub fn new(file: File, len: usize) -> io::Result<Self> {
+        if (file.metadata()?.len() as usize) < len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        // SAFETY: 映射期间文件不会被本库以其他方式修改或截断（见类型文档）
+        let map = unsafe { MmapOptions::new().len(len).map_mut(&file)? };
+        Ok(Self { map })
+    }
+
+    /// 映射内容的只读视图
+    pub fn as_slice(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// 映射内容的可写视图
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.map
+    }
+
+    fn range_mut(&mut self, offset: u32, length: usize) -> io::Result<&mut [u8]> {
+        let start = offset as usize;
+        self.as_mut_slice()
+            .get_mut(start..start + length)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))
+    }
+}
+
+impl FlashBackend for MmapBackend {
+    fn read_at(&mut self, offset: u32, buf: &mut [u8]) -> io::Result<()> {
+        buf.copy_from_slice(self.range_mut(offset, buf.len())?);
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u32, data: &[u8]) -> io::Result<()> {
+        self.range_mut(offset, data.len())?.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn fill(&mut self, offset: u32, length: usize, value: u8) -> io::Result<()> {
+        self.range_mut(offset, length)?.fill(value);
+        Ok(())
+    }
+
+    fn is_filled(&mut self, offset: u32, length: usize, value: u8) -> io::Result<bool> {
+        Ok(self
+            .range_mut(offset, length)?
+            .iter()
+            .all(|&byte| byte == value))
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        // 同步写回映射的脏页
+        self.map.flush()
+    }
+
+    fn as_slice(&self) -> Option<&[u8]> {
+        Some(MmapBackend::as_slice(self))
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{ErasedValue, FlashMock, FlashMockConfigError, ProgramMode};
+use embedded_storage::nor_flash::{ErrorType, MultiwriteNorFlash, NorFlash, ReadNorFlash};
+use std::ops::{Deref, DerefMut};
+
+/// 允许重复编程同一个字的`FlashMock`，实现`MultiwriteNorFlash`
+///
+/// 内部`FlashMock`工作在`ProgramMode::BitwiseAnd`模式：重复写入时结果为旧数据与新数据
+/// 按位与（只能把1改为0），不再检查目标区域是否已擦除。`MultiwriteNorFlash`约定擦除值为0xFF，
+/// 因此只能包装擦除值为`ErasedValue::Ones`的`FlashMock`。
+/// 通过`Deref`可以访问内部`FlashMock`的全部方法。
+pub struct MultiwriteFlashMock<
+    const READ_SIZE: usize,
+    const WRITE_SIZE: usize,
+    const ERASE_SIZE: usize,
+>(pub(crate) FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>);
+
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize>
+    MultiwriteFlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+    /// 将`flash`切换为按位与编程模式（已是该模式时保留原有的编程次数限制，
+    /// 先擦后写与ECC模式都不允许重复编程，一律切换为不限次数的按位与）
+    ///
+    /// `flash`的擦除值不是0xFF时返回`FlashMockConfigError::UnsupportedErasedValue`。
+    pub fn new(
+        mut flash: FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>,
+    ) -> Result<Self, FlashMockConfigError> {
+        let erased_value = flash.erased_value();
+        if erased_value != ErasedValue::Ones {
+            return Err(FlashMockConfigError::UnsupportedErasedValue { erased_value });
+        }
+        if !matches!(flash.program_mode(), ProgramMode::BitwiseAnd { .. }) {
+            flash.set_program_mode(ProgramMode::BitwiseAnd { max_programs: None });
+        }
+        Ok(Self(flash))
+    }
+
+    /// 取回内部的`FlashMock`（恢复先擦后写模式）
+    pub fn into_inner(mut self) -> FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE> {
+        self.0.set_program_mode(ProgramMode::EraseBeforeWrite);
+        self.0
+    }
+}
+
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> Deref
+    for MultiwriteFlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+    type Target = FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> DerefMut
+    for MultiwriteFlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> ErrorType
+    for MultiwriteFlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+    type Error = <FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE> as ErrorType>::Error;
+}
+
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> ReadNorFlash
+    for MultiwriteFlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+    const READ_SIZE: usize = READ_SIZE;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        ReadNorFlash::read(&mut self.0, offset, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        ReadNorFlash::capacity(&self.0)
+    }
+}
+
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> NorFlash
+    for MultiwriteFlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+    const WRITE_SIZE: usize = WRITE_SIZE;
+    const ERASE_SIZE: usize = ERASE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        NorFlash::erase(&mut self.0, from, to)
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        NorFlash::write(&mut self.0, offset, bytes)
+    }
+}
+
+impl<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize> MultiwriteNorFlash
+    for MultiwriteFlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ecc_mode_is_switched_to_bitwise_and() {
+        let mut flash = FlashMock::<1, 4, 256>::new_in_memory(1024).unwrap();
+        flash.set_program_mode(ProgramMode::Ecc);
+        let mut flash = MultiwriteFlashMock::new(flash).unwrap();
+        assert_eq!(
+            flash.program_mode(),
+            ProgramMode::BitwiseAnd { max_programs: None }
+        );
+        NorFlash::write(&mut flash, 0, &[0xF0; 4]).unwrap();
+        NorFlash::write(&mut flash, 0, &[0x3C; 4]).unwrap();
+        let mut buf = [0u8; 4];
+        ReadNorFlash::read(&mut flash, 0, &mut buf).unwrap();
+        assert_eq!(buf, [0x30; 4]);
+    }
+
+    #[test]
+    fn rejects_zeros_erased_value() {
+        let options = crate::FlashOptions::new().erased_value(ErasedValue::Zeros);
+        let flash = FlashMock::<1, 4, 256>::new_in_memory_with(1024, options).unwrap();
+        assert!(matches!(
+            MultiwriteFlashMock::new(flash),
+            Err(FlashMockConfigError::UnsupportedErasedValue {
+                erased_value: ErasedValue::Zeros
+            })
+        ));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::FlashMockConfigError;
+use std::{fs, path::Path};
+
+/// 打开已有镜像文件时，文件长度与总容量不一致的处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SizePolicy {
+    /// 长度不一致即报错
+    #[default]
+    Reject,
+    /// 文件较短时在末尾补擦除值（较长仍报错）
+    Grow,
+    /// 文件较长时截断多余数据（较短仍报错）
+    Truncate,
+}
+
+/// 擦除后的字节值，同时决定编程方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErasedValue {
+    /// 擦除为0xFF，编程只能把1改为0（绝大多数NOR Flash）
+    #[default]
+    Ones,
+    /// 擦除为0x00，编程只能把0改为1（部分MCU内部Flash、EEPROM仿真）
+    Zeros,
+}
+
+impl ErasedValue {
+    /// 擦除后的字节值
+    pub fn byte(self) -> u8 {
+        match self {
+            ErasedValue::Ones => 0xFF,
+            ErasedValue::Zeros => 0x00,
+        }
+    }
+
+    /// 在`old`上编程`new`后的结果（只能从擦除态翻转为编程态）
+    pub fn program(self, old: u8, new: u8) -> u8 {
+        match self {
+            ErasedValue::Ones => old & new,
+            ErasedValue::Zeros => old | new,
+        }
+    }
+}
+
+/// `FlashMock`的打开选项
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlashOptions {
+    pub(crate) size_policy: SizePolicy,
+    pub(crate) erased_value: ErasedValue,
+    pub(crate) geometry_sidecar: bool,
+    #[cfg(unix)]
+    pub(crate) mmap: bool,
+}
+
+impl FlashOptions {
+    /// 默认选项：长度不一致报错，不使用几何信息文件，普通文件后端，擦除为0xFF
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置文件长度不一致时的处理策略
+    pub fn size_policy(mut self, policy: SizePolicy) -> Self {
+        self.size_policy = policy;
+        self
+    }
+
+    /// 设置擦除后的字节值（新建镜像、擦除、`SizePolicy::Grow`补齐均使用该值）
+    pub fn erased_value(mut self, erased_value: ErasedValue) -> Self {
+        self.erased_value = erased_value;
+        self
+    }
+
+    /// 是否在`<path>.geometry`中记录并校验几何参数（READ/WRITE/ERASE大小与容量），
+    /// 用不同的const泛型参数重新打开同一镜像时会报错
+    pub fn geometry_sidecar(mut self, enabled: bool) -> Self {
+        self.geometry_sidecar = enabled;
+        self
+    }
+
+    /// 是否以内存映射方式打开镜像文件
+    #[cfg(unix)]
+    pub fn mmap(mut self, enabled: bool) -> Self {
+        self.mmap = enabled;
+        self
+    }
+}
+
+/// 镜像的几何参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    /// 最小读取单位
+    pub read_size: usize,
+    /// 最小写入单位
+    pub write_size: usize,
+    /// 最小擦除单位
+    pub erase_size: usize,
+    /// 总存储容量
+    pub capacity: usize,
+}
+
+impl Geometry {
+    /// 几何信息文件路径：`<path>.geometry`
+    fn sidecar_path(path: &str) -> String {
+        format!("{path}.geometry")
+    }
+
+    /// 读取几何信息文件（不存在则返回`None`）
+    pub(crate) fn load(path: &str) -> Result<Option<Self>, FlashMockConfigError> {
+        let sidecar = Self::sidecar_path(path);
+        if !Path::new(&sidecar).exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&sidecar)?;
+        let field = |name: &'static str| {
+            text.lines()
+                .filter_map(|line| line.split_once('='))
+                .find(|(key, _)| key.trim() == name)
+                .and_then(|(_, value)| value.trim().parse().ok())
+                .ok_or_else(|| FlashMockConfigError::InvalidGeometryFile {
+                    path: sidecar.clone(),
+                    field: name,
+                })
+        };
+        Ok(Some(Self {
+            read_size: field("read_size")?,
+            write_size: field("write_size")?,
+            erase_size: field("erase_size")?,
+            capacity: field("capacity")?,
+        }))
+    }
+
+    /// 写入几何信息文件
+    pub(crate) fn store(&self, path: &str) -> Result<(), FlashMockConfigError> {
+        fs::write(
+            Self::sidecar_path(path),
+            format!(
+                "read_size={}\nwrite_size={}\nerase_size={}\ncapacity={}\n",
+                self.read_size, self.write_size, self.erase_size, self.capacity
+            ),
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+/// 数据保持（retention）衰减模型
+///
+/// 擦除次数达到`min_cycles`的块，自上次擦除起每经过一个`period`，
+/// 就有`bits_per_period`个确定性挑选的位漂移回擦除态。按块计时，块内后续的编程
+/// 不会重新计时，新写入的数据从下一个周期起参与衰减。时间取自`FlashMock`的模拟时钟
+/// （`advance_time`或耗时模型推进），衰减在读写该块时才计算并写入存储。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    /// 擦除次数达到该值的块才会发生衰减
+    pub min_cycles: u32,
+    /// 衰减周期
+    pub period: Duration,
+    /// 每个周期漂移回擦除态的位数
+    pub bits_per_period: u32,
+}
+
+/// 每个擦除块的保持状态
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BlockRetention {
+    /// 上次擦除的模拟时间
+    pub(crate) since: Duration,
+    /// 自`since`以来已施加的衰减周期数
+    pub(crate) applied: u64,
+}
+
+impl Retention {
+    /// 自`since`到`now`应施加的衰减周期数
+    pub(crate) fn periods(&self, since: Duration, now: Duration) -> u64 {
+        if self.period.is_zero() {
+            return 0;
+        }
+        (now.saturating_sub(since).as_nanos() / self.period.as_nanos()) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FlashMock;
+    use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+    const RETENTION: Retention = Retention {
+        min_cycles: 1,
+        period: Duration::from_secs(10),
+        bits_per_period: 32,
+    };
+
+    /// 擦除一次块0并写满前半块0x00，按需每5秒向后半块追加一个字，100秒后读出前半块
+    fn decayed(append: bool) -> Vec<u8> {
+        let mut flash = FlashMock::<1, 4, 4096>::new_in_memory(4096).unwrap();
+        flash.set_retention(Some(RETENTION));
+        flash.erase(0, 4096).unwrap();
+        flash.write(0, &[0x00; 2048]).unwrap();
+        for step in 0..20 {
+            flash.advance_time(Duration::from_secs(5));
+            if append {
+                flash.write(2048 + step * 4, &[0x00; 4]).unwrap();
+            }
+        }
+        let mut data = vec![0u8; 2048];
+        flash.read(0, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn decays_with_elapsed_periods() {
+        let data = decayed(false);
+        let flipped: u32 = data.iter().map(|byte| byte.count_ones()).sum();
+        // 10个周期共挑选320个位，约一半落在前半块（可能重复）
+        assert!(flipped > 0 && flipped <= 10 * RETENTION.bits_per_period);
+    }
+
+    #[test]
+    fn appending_does_not_restart_decay() {
+        assert_eq!(decayed(true), decayed(false));
+    }
+
+    #[test]
+    fn no_decay_below_min_cycles_or_after_erase() {
+        let mut flash = FlashMock::<1, 4, 4096>::new_in_memory(4096).unwrap();
+        flash.set_retention(Some(RETENTION));
+        flash.write(0, &[0x00; 4096]).unwrap();
+        flash.advance_time(Duration::from_secs(100));
+        let mut data = vec![0u8; 4096];
+        flash.read(0, &mut data).unwrap();
+        assert!(data.iter().all(|&byte| byte == 0x00));
+
+        // 擦除重新计时：刚擦除并写入的数据在一个周期内不衰减
+        flash.erase(0, 4096).unwrap();
+        flash.write(0, &[0x00; 4096]).unwrap();
+        flash.advance_time(Duration::from_secs(9));
+        flash.read(0, &mut data).unwrap();
+        assert!(data.iter().all(|&byte| byte == 0x00));
+        flash.advance_time(Duration::from_secs(1));
+        flash.read(0, &mut data).unwrap();
+        let flipped: u32 = data.iter().map(|byte| byte.count_ones()).sum();
+        assert!(flipped > 0 && flipped <= RETENTION.bits_per_period);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{EccWord, FlashStats, Geometry, retention::BlockRetention};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// `FlashMock`某一时刻的完整状态（内容与磨损计数），由`FlashMock::snapshot`创建
+///
+/// 内容按擦除块保存为共享的`Arc<[u8]>`：快照之间、快照与`FlashMock`之间未修改的块
+/// 共用同一份数据，克隆快照只复制块指针。
+#[derive(Debug, Clone)]
+pub struct FlashSnapshot {
+    pub(crate) geometry: Geometry,
+    pub(crate) blocks: Vec<Arc<[u8]>>,
+    pub(crate) erase_cycles: Vec<u32>,
+    pub(crate) stuck_bits: BTreeMap<u32, u8>,
+    pub(crate) stats: FlashStats,
+    pub(crate) word_programs: BTreeMap<u32, u32>,
+    pub(crate) ecc_words: BTreeMap<u32, EccWord>,
+    pub(crate) block_retention: Vec<BlockRetention>,
+}
+
+impl FlashSnapshot {
+    /// 快照的总容量
+    pub fn capacity(&self) -> usize {
+        self.geometry.capacity
+    }
+
+    /// 快照来源实例的几何参数
+    pub fn geometry(&self) -> Geometry {
+        self.geometry
+    }
+
+    /// 快照时每个擦除块累计的擦除次数
+    pub fn erase_cycles(&self) -> &[u32] {
+        &self.erase_cycles
+    }
+
+    /// 快照时的使用统计
+    pub fn stats(&self) -> &FlashStats {
+        &self.stats
+    }
+
+    /// 拼接出完整的镜像内容
+    pub fn to_image(&self) -> Vec<u8> {
+        self.blocks.concat()
+    }
+}
+
+/// 写时复制的块状态：最近一次快照/恢复时的块内容，以及此后被修改过的块
+#[derive(Debug, Default)]
+pub(crate) struct CowBlocks {
+    pub(crate) base: Option<Vec<Arc<[u8]>>>,
+    pub(crate) dirty: Vec<bool>,
+}
+
+impl CowBlocks {
+    /// 标记`blocks`中的块已被修改
+    pub(crate) fn mark(&mut self, blocks: std::ops::Range<usize>) {
+        if self.base.is_some() {
+            self.dirty[blocks].fill(true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        BitFlipMode, BitFlips, FlashBackend, FlashMock, FlashSnapshot, MemoryBackend, Retention,
+    };
+    use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    type Flash = FlashMock<1, 4, 256>;
+
+    /// 记录每次写入起始地址的内存后端
+    struct LoggingBackend {
+        inner: MemoryBackend,
+        writes: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl FlashBackend for LoggingBackend {
+        fn read_at(&mut self, offset: u32, buf: &mut [u8]) -> io::Result<()> {
+            self.inner.read_at(offset, buf)
+        }
+
+        fn write_at(&mut self, offset: u32, data: &[u8]) -> io::Result<()> {
+            self.writes.lock().unwrap().push(offset);
+            self.inner.write_at(offset, data)
+        }
+    }
+
+    fn logged_flash() -> (Flash, Arc<Mutex<Vec<u32>>>) {
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let backend = LoggingBackend {
+            inner: MemoryBackend::new(vec![0xFF; 1024]),
+            writes: Arc::clone(&writes),
+        };
+        (Flash::with_backend(backend, 1024).unwrap(), writes)
+    }
+
+    fn image(flash: &mut Flash) -> Vec<u8> {
+        let mut image = vec![0u8; 1024];
+        flash.read(0, &mut image).unwrap();
+        image
+    }
+
+    /// 快照中与`other`共用数据的块
+    fn shared(snapshot: &FlashSnapshot, other: &FlashSnapshot) -> Vec<bool> {
+        snapshot
+            .blocks
+            .iter()
+            .zip(&other.blocks)
+            .map(|(a, b)| Arc::ptr_eq(a, b))
+            .collect()
+    }
+
+    #[test]
+    fn branch_and_restore_rewrites_only_changed_blocks() {
+        let (mut flash, writes) = logged_flash();
+        flash.write(0, &[0xA0; 4]).unwrap();
+        let a = flash.snapshot().unwrap();
+        flash.write(256, &[0xB1; 4]).unwrap();
+        let b = flash.snapshot().unwrap();
+        assert_eq!(shared(&a, &b), [true, false, true, true]);
+
+        writes.lock().unwrap().clear();
+        flash.restore(&a).unwrap();
+        assert_eq!(*writes.lock().unwrap(), [256]);
+        assert_eq!(image(&mut flash), a.to_image());
+
+        writes.lock().unwrap().clear();
+        flash.restore(&b).unwrap();
+        assert_eq!(*writes.lock().unwrap(), [256]);
+        assert_eq!(image(&mut flash), b.to_image());
+
+        // 未修改时重复恢复不写入任何块
+        writes.lock().unwrap().clear();
+        flash.restore(&b).unwrap();
+        assert!(writes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn restore_after_erase() {
+        let (mut flash, writes) = logged_flash();
+        flash.write(512, &[0x12; 8]).unwrap();
+        let before = flash.snapshot().unwrap();
+        flash.erase(512, 768).unwrap();
+        assert_eq!(flash.erase_cycles()[2], 1);
+
+        writes.lock().unwrap().clear();
+        flash.restore(&before).unwrap();
+        assert_eq!(*writes.lock().unwrap(), [512]);
+        assert_eq!(image(&mut flash), before.to_image());
+        assert_eq!(flash.erase_cycles()[2], 0);
+        // 恢复后被擦除的块不再是已擦除状态
+        assert!(flash.write(512, &[0x00; 4]).is_err());
+    }
+
+    #[test]
+    fn bit_flips_and_retention_mark_blocks_dirty() {
+        let mut flash = Flash::new_in_memory(1024).unwrap();
+        flash.erase(768, 1024).unwrap();
+        flash.write(768, &[0x00; 256]).unwrap();
+        let clean = flash.snapshot().unwrap();
+
+        // 读干扰直接写入存储
+        flash
+            .set_bit_flips(Some(BitFlips {
+                targets: vec![(300, 0x01)],
+                mode: BitFlipMode::Persistent,
+                ..Default::default()
+            }))
+            .unwrap();
+        flash.set_bit_flips(None).unwrap();
+
+        // 数据保持衰减在读取时写入存储
+        flash.set_retention(Some(Retention {
+            min_cycles: 1,
+            period: Duration::from_secs(1),
+            bits_per_period: 8,
+        }));
+        flash.advance_time(Duration::from_secs(10));
+        let decayed = image(&mut flash);
+        assert_ne!(decayed[768..], clean.to_image()[768..]);
+
+        let dirty = flash.snapshot().unwrap();
+        assert_eq!(shared(&clean, &dirty), [true, false, true, false]);
+        assert_eq!(dirty.to_image(), decayed);
+
+        flash.set_retention(None);
+        flash.restore(&clean).unwrap();
+        assert_eq!(image(&mut flash), clean.to_image());
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+/// 使用统计（擦写次数与读写字节数），用于磨损均衡等测试断言
+///
+/// 统计只保存在内存中，随`FlashMock`实例创建而清零，不写入镜像文件。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlashStats {
+    /// 每个擦除块的擦除次数（下标为块编号）
+    pub erase_counts: Vec<u32>,
+    /// 每个擦除块的编程（write）次数（下标为块编号）
+    pub program_counts: Vec<u32>,
+    /// 累计读取字节数
+    pub bytes_read: u64,
+    /// 累计写入字节数
+    pub bytes_written: u64,
+    /// 累计擦除字节数
+    pub bytes_erased: u64,
+    /// 按耗时模型累计的忙碌时间
+    pub busy_time: Duration,
+}
+
+impl FlashStats {
+    /// 创建`blocks`个擦除块的空统计
+    pub(crate) fn new(blocks: usize) -> Self {
+        Self {
+            erase_counts: vec![0; blocks],
+            program_counts: vec![0; blocks],
+            ..Self::default()
+        }
+    }
+
+    /// 所有块中最大的擦除次数
+    pub fn max_erase_count(&self) -> u32 {
+        self.erase_counts.iter().copied().max().unwrap_or(0)
+    }
+
+    /// 所有块中最小的擦除次数
+    pub fn min_erase_count(&self) -> u32 {
+        self.erase_counts.iter().copied().min().unwrap_or(0)
+    }
+
+    /// 平均擦除次数
+    pub fn mean_erase_count(&self) -> f64 {
+        if self.erase_counts.is_empty() {
+            return 0.0;
+        }
+        self.total_erases() as f64 / self.erase_counts.len() as f64
+    }
+
+    /// 所有块的擦除次数之和
+    pub fn total_erases(&self) -> u64 {
+        self.erase_counts.iter().map(|&count| count as u64).sum()
+    }
+
+    /// 擦除次数最多的块编号（并列时取编号最小者，从未擦除过则为`None`）
+    pub fn hottest_sector(&self) -> Option<usize> {
+        let max = self.max_erase_count();
+        if max == 0 {
+            return None;
+        }
+        self.erase_counts.iter().position(|&count| count == max)
+    }
+
+    /// 记录一次读取
+    pub(crate) fn record_read(&mut self, length: usize) {
+        self.bytes_read += length as u64;
+    }
+
+    /// 记录一次写入，`[offset, offset+length)`覆盖到的块编程次数各加一
+    pub(crate) fn record_program(&mut self, offset: u32, length: usize, erase_size: usize) {
+        self.bytes_written += length as u64;
+        for block in blocks_in(offset, length, erase_size) {
+            self.program_counts[block] += 1;
+        }
+    }
+
+    /// 记录一次操作的耗时
+    pub(crate) fn record_busy(&mut self, duration: Duration) {
+        self.busy_time += duration;
+    }
+
+    /// 记录一次擦除，`[from, from+length)`覆盖到的块擦除次数各加一
+    pub(crate) fn record_erase(&mut self, from: u32, length: usize, erase_size: usize) {
+        self.bytes_erased += length as u64;
+        for block in blocks_in(from, length, erase_size) {
+            self.erase_counts[block] += 1;
+        }
+    }
+}
+
+/// `[offset, offset+length)`覆盖到的擦除块编号
+pub(crate) fn blocks_in(offset: u32, length: usize, erase_size: usize) -> std::ops::Range<usize> {
+    if length == 0 {
+        return 0..0;
+    }
+    let start = offset as usize;
+    start / erase_size..(start + length).div_ceil(erase_size)
+}
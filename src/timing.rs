@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+/// 操作耗时模型（参考芯片手册的时序参数）
+///
+/// 擦除耗时按范围拆分计算：整片擦除使用`chip_erase`，其余部分优先按对齐的`block_size`
+/// 计为块擦除，剩下的按ERASE_SIZE计为扇区擦除。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TimingProfile {
+    /// 页大小（0表示按WRITE_SIZE计）
+    pub page_size: usize,
+    /// 编程一页的耗时，写入覆盖到的每一页各计一次
+    pub page_program: Duration,
+    /// 擦除一个ERASE_SIZE扇区的耗时
+    pub sector_erase: Duration,
+    /// 块大小（需是ERASE_SIZE的整数倍，0表示没有块擦除）
+    pub block_size: usize,
+    /// 擦除一个块的耗时
+    pub block_erase: Duration,
+    /// 整片擦除的耗时（零表示按块/扇区累加）
+    pub chip_erase: Duration,
+    /// 读取吞吐量（字节/秒，0表示读取不耗时）
+    pub read_bytes_per_sec: u64,
+}
+
+impl TimingProfile {
+    /// Winbond W25Q128JV典型值：256字节页编程0.7ms、4KB扇区擦除45ms、64KB块擦除150ms、
+    /// 整片擦除40s、133MHz单线快速读取
+    pub fn w25q128_typical() -> Self {
+        Self {
+            page_size: 256,
+            page_program: Duration::from_micros(700),
+            sector_erase: Duration::from_millis(45),
+            block_size: 64 * 1024,
+            block_erase: Duration::from_millis(150),
+            chip_erase: Duration::from_secs(40),
+            read_bytes_per_sec: 133_000_000 / 8,
+        }
+    }
+
+    /// Winbond W25Q128JV最大值：页编程3ms、扇区擦除400ms、块擦除2s、整片擦除200s，
+    /// 用于验证最坏情况下的时延预算
+    pub fn w25q128_max() -> Self {
+        Self {
+            page_program: Duration::from_millis(3),
+            sector_erase: Duration::from_millis(400),
+            block_erase: Duration::from_secs(2),
+            chip_erase: Duration::from_secs(200),
+            ..Self::w25q128_typical()
+        }
+    }
+
+    /// 读取`length`字节的耗时
+    pub fn read_time(&self, length: usize) -> Duration {
+        if self.read_bytes_per_sec == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos(
+            (length as u128 * 1_000_000_000 / self.read_bytes_per_sec as u128) as u64,
+        )
+    }
+
+    /// 编程`[offset, offset+length)`的耗时
+    pub fn program_time(&self, offset: u32, length: usize, write_size: usize) -> Duration {
+        if length == 0 {
+            return Duration::ZERO;
+        }
+        let page = if self.page_size == 0 {
+            write_size
+        } else {
+            self.page_size
+        };
+        let first = offset as usize / page;
+        let last = (offset as usize + length - 1) / page;
+        self.page_program * (last - first + 1) as u32
+    }
+
+    /// 擦除`[from, from+length)`的耗时
+    pub fn erase_time(
+        &self,
+        from: u32,
+        length: usize,
+        erase_size: usize,
+        capacity: usize,
+    ) -> Duration {
+        if from == 0 && length == capacity && !self.chip_erase.is_zero() {
+            return self.chip_erase;
+        }
+        let end = from as usize + length;
+        let mut address = from as usize;
+        let mut total = Duration::ZERO;
+        while address < end {
+            if self.block_size > 0
+                && address.is_multiple_of(self.block_size)
+                && address + self.block_size <= end
+            {
+                total += self.block_erase;
+                address += self.block_size;
+            } else {
+                total += self.sector_erase;
+                address += erase_size;
+            }
+        }
+        total
+    }
+}
@@ -0,0 +1,572 @@
+use crate::{FlashMock, FlashMockError, Geometry, PowerCut};
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
+use thiserror::Error;
+
+/// 日志文件头的魔数
+const JOURNAL_MAGIC: &[u8; 4] = b"FMJ2";
+
+/// 跟踪记录中保存数据的详细程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TraceDetail {
+    /// 只保存读写数据的64位哈希（无法重放写入）
+    #[default]
+    Hash,
+    /// 保存完整的读写数据（可重放）
+    Full,
+}
+
+/// 被跟踪的操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceOp {
+    /// `read`
+    Read,
+    /// `write`
+    Write,
+    /// `erase`（`to`为调用时传入的结束地址，`length`为擦除范围长度，范围无效时为0）
+    Erase { to: u32 },
+    /// `power_cycle`
+    PowerCycle,
+}
+
+/// 跟踪记录中保存的数据
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceData {
+    /// 无数据（擦除、上电）
+    None,
+    /// 读出或写入数据的FNV-1a哈希
+    Hash(u64),
+    /// 读出或写入的完整数据
+    Full(Vec<u8>),
+}
+
+/// 操作结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceResult {
+    /// 成功
+    Ok,
+    /// 掉电（重放时据此在相同位置注入掉电）
+    PowerLoss { offset: u32 },
+    /// 其他错误（错误信息）
+    Failed(String),
+}
+
+impl From<&Result<(), FlashMockError>> for TraceResult {
+    fn from(result: &Result<(), FlashMockError>) -> Self {
+        match result {
+            Ok(()) => TraceResult::Ok,
+            Err(FlashMockError::PowerLoss { offset }) => TraceResult::PowerLoss { offset: *offset },
+            Err(error) => TraceResult::Failed(error.to_string()),
+        }
+    }
+}
+
+/// 一条跟踪记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// 操作类型
+    pub op: TraceOp,
+    /// 起始地址
+    pub offset: u32,
+    /// 长度
+    pub length: u32,
+    /// 读出或写入的数据
+    pub data: TraceData,
+    /// 操作结果
+    pub result: TraceResult,
+}
+
+/// 从磁盘读回的操作日志
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Journal {
+    /// 记录日志的`FlashMock`几何参数
+    pub geometry: Geometry,
+    /// 按顺序排列的跟踪记录
+    pub entries: Vec<TraceEntry>,
+}
+
+/// 重放错误
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("Journal geometry {journal:?} does not match flash geometry {flash:?}")]
+    GeometryMismatch { journal: Geometry, flash: Geometry },
+    #[error("Entry {index} has no full data to replay (record with TraceDetail::Full)")]
+    MissingData { index: usize },
+    #[error("Entry {index} diverged: expected {expected:?}, got {actual:?}")]
+    Diverged {
+        index: usize,
+        expected: TraceResult,
+        actual: TraceResult,
+    },
+    #[error("Entry {index} read back different data")]
+    ReadMismatch { index: usize },
+}
+
+/// 日志文件错误
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Not a flash journal (bad magic)")]
+    BadMagic,
+    #[error("Truncated journal at byte {position}")]
+    Truncated { position: usize },
+    #[error("Invalid journal entry at byte {position}")]
+    InvalidEntry { position: usize },
+    #[error("Journal entry at byte {position} has an impossible address range")]
+    InvalidRange { position: usize },
+}
+
+/// 64位FNV-1a哈希
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+/// `FlashMock`内部的跟踪记录器
+pub(crate) struct Tracer {
+    pub(crate) detail: TraceDetail,
+    pub(crate) entries: Vec<TraceEntry>,
+    journal: Option<File>,
+    journal_error: Option<io::Error>,
+}
+
+impl Tracer {
+    /// 创建记录器，`journal`不为`None`时创建日志文件并写入文件头
+    pub(crate) fn new(
+        detail: TraceDetail,
+        journal: Option<&Path>,
+        geometry: Geometry,
+    ) -> io::Result<Self> {
+        let journal = match journal {
+            Some(path) => {
+                let mut file = File::create(path)?;
+                file.write_all(&encode_header(geometry))?;
+                Some(file)
+            }
+            None => None,
+        };
+        Ok(Self {
+            detail,
+            entries: Vec::new(),
+            journal,
+            journal_error: None,
+        })
+    }
+
+    /// 按详细程度整理数据
+    pub(crate) fn data(&self, bytes: &[u8]) -> TraceData {
+        match self.detail {
+            TraceDetail::Hash => TraceData::Hash(fnv1a(bytes)),
+            TraceDetail::Full => TraceData::Full(bytes.to_vec()),
+        }
+    }
+
+    /// 追加一条记录（日志文件写入失败时记下第一个错误，停止跟踪时返回）
+    pub(crate) fn record(&mut self, entry: TraceEntry) {
+        if let Some(file) = &mut self.journal
+            && self.journal_error.is_none()
+            && let Err(error) = file.write_all(&encode(&entry))
+        {
+            self.journal_error = Some(error);
+        }
+        self.entries.push(entry);
+    }
+
+    /// 结束跟踪，返回内存中的记录
+    pub(crate) fn finish(self) -> Result<Vec<TraceEntry>, JournalError> {
+        if let Some(error) = self.journal_error {
+            return Err(error.into());
+        }
+        if let Some(file) = self.journal {
+            file.sync_all()?;
+        }
+        Ok(self.entries)
+    }
+}
+
+/// 编码日志文件头：魔数与几何参数
+fn encode_header(geometry: Geometry) -> Vec<u8> {
+    let mut header = JOURNAL_MAGIC.to_vec();
+    header.extend_from_slice(&(geometry.read_size as u32).to_le_bytes());
+    header.extend_from_slice(&(geometry.write_size as u32).to_le_bytes());
+    header.extend_from_slice(&(geometry.erase_size as u32).to_le_bytes());
+    header.extend_from_slice(&(geometry.capacity as u64).to_le_bytes());
+    header
+}
+
+/// 编码一条记录
+///
+/// 标志字节：低2位为操作类型，2~3位为数据类型，4~5位为结果类型；
+/// 随后是offset、length（u32小端）、擦除的结束地址（仅擦除，u32）、
+/// 数据（哈希为u64，完整数据为length字节）、
+/// 结果附加信息（掉电地址u32，或u16长度的错误信息）。
+fn encode(entry: &TraceEntry) -> Vec<u8> {
+    let op = match entry.op {
+        TraceOp::Read => 0,
+        TraceOp::Write => 1,
+        TraceOp::Erase { .. } => 2,
+        TraceOp::PowerCycle => 3,
+    };
+    let data = match entry.data {
+        TraceData::None => 0,
+        TraceData::Hash(_) => 1,
+        TraceData::Full(_) => 2,
+    };
+    let result = match entry.result {
+        TraceResult::Ok => 0,
+        TraceResult::PowerLoss { .. } => 1,
+        TraceResult::Failed(_) => 2,
+    };
+    let mut bytes = vec![op | data << 2 | result << 4];
+    bytes.extend_from_slice(&entry.offset.to_le_bytes());
+    bytes.extend_from_slice(&entry.length.to_le_bytes());
+    if let TraceOp::Erase { to } = entry.op {
+        bytes.extend_from_slice(&to.to_le_bytes());
+    }
+    match &entry.data {
+        TraceData::None => {}
+        TraceData::Hash(hash) => bytes.extend_from_slice(&hash.to_le_bytes()),
+        TraceData::Full(data) => bytes.extend_from_slice(data),
+    }
+    match &entry.result {
+        TraceResult::Ok => {}
+        TraceResult::PowerLoss { offset } => bytes.extend_from_slice(&offset.to_le_bytes()),
+        TraceResult::Failed(message) => {
+            let message = &message.as_bytes()[..message.floor_char_boundary(u16::MAX as usize)];
+            bytes.extend_from_slice(&(message.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(message);
+        }
+    }
+    bytes
+}
+
+/// 日志解析游标
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    /// 已读取的字节数（用于错误信息）
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], JournalError> {
+        if self.bytes.len() < length {
+            return Err(JournalError::Truncated {
+                position: self.position,
+            });
+        }
+        let (head, tail) = self.bytes.split_at(length);
+        self.bytes = tail;
+        self.position += length;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, JournalError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, JournalError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, JournalError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+/// 解码一条记录
+fn decode(cursor: &mut Cursor) -> Result<TraceEntry, JournalError> {
+    let position = cursor.position;
+    let invalid = || JournalError::InvalidEntry { position };
+    let flags = cursor.take(1)?[0];
+    let offset = cursor.u32()?;
+    let length = cursor.u32()?;
+    let op = match flags & 0b11 {
+        0 => TraceOp::Read,
+        1 => TraceOp::Write,
+        2 => TraceOp::Erase { to: cursor.u32()? },
+        _ => TraceOp::PowerCycle,
+    };
+    let data = match (flags >> 2) & 0b11 {
+        0 => TraceData::None,
+        1 => TraceData::Hash(cursor.u64()?),
+        2 => TraceData::Full(cursor.take(length as usize)?.to_vec()),
+        _ => return Err(invalid()),
+    };
+    let result = match (flags >> 4) & 0b11 {
+        0 => TraceResult::Ok,
+        1 => TraceResult::PowerLoss {
+            offset: cursor.u32()?,
+        },
+        2 => {
+            let length = cursor.u16()? as usize;
+            let message =
+                String::from_utf8(cursor.take(length)?.to_vec()).map_err(|_| invalid())?;
+            TraceResult::Failed(message)
+        }
+        _ => return Err(invalid()),
+    };
+    // 记录的范围必须与操作一致：成功的读写不会越过地址空间，擦除长度由结束地址决定
+    let consistent = match op {
+        TraceOp::Read | TraceOp::Write => {
+            offset.checked_add(length).is_some() || matches!(result, TraceResult::Failed(_))
+        }
+        TraceOp::Erase { to } => length == to.saturating_sub(offset),
+        TraceOp::PowerCycle => offset == 0 && length == 0,
+    };
+    if !consistent {
+        return Err(JournalError::InvalidRange { position });
+    }
+    Ok(TraceEntry {
+        op,
+        offset,
+        length,
+        data,
+        result,
+    })
+}
+
+impl Journal {
+    /// 读取磁盘上的操作日志
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, JournalError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// 解析日志文件内容
+    fn from_bytes(bytes: &[u8]) -> Result<Self, JournalError> {
+        let mut cursor = Cursor::new(bytes);
+        if cursor.take(JOURNAL_MAGIC.len())? != JOURNAL_MAGIC {
+            return Err(JournalError::BadMagic);
+        }
+        let geometry = Geometry {
+            read_size: cursor.u32()? as usize,
+            write_size: cursor.u32()? as usize,
+            erase_size: cursor.u32()? as usize,
+            capacity: cursor.u64()? as usize,
+        };
+        let mut entries = Vec::new();
+        while !cursor.bytes.is_empty() {
+            entries.push(decode(&mut cursor)?);
+        }
+        Ok(Self { geometry, entries })
+    }
+
+    /// 在`flash`上按顺序重放日志（`flash`应与记录时的初始内容和配置一致）
+    ///
+    /// 掉电的操作会在相同位置注入掉电，`PowerCycle`记录会重新上电；
+    /// 每条记录的结果和读出数据都必须与日志一致，否则返回第一处差异。
+    pub fn replay<const READ_SIZE: usize, const WRITE_SIZE: usize, const ERASE_SIZE: usize>(
+        &self,
+        flash: &mut FlashMock<READ_SIZE, WRITE_SIZE, ERASE_SIZE>,
+    ) -> Result<(), ReplayError> {
+        let geometry = flash.geometry();
+        if geometry != self.geometry {
+            return Err(ReplayError::GeometryMismatch {
+                journal: self.geometry,
+                flash: geometry,
+            });
+        }
+        for (index, entry) in self.entries.iter().enumerate() {
+            // 原操作中途掉电：在相同的字节数处注入掉电
+            if let TraceResult::PowerLoss { offset } = entry.result
+                && !flash.is_powered_off()
+            {
+                let done = offset.saturating_sub(entry.offset) as usize;
+                flash.set_power_cut(Some(PowerCut::AfterBytes(done)));
+            }
+            let result = match entry.op {
+                TraceOp::Read => {
+                    let mut bytes = vec![0u8; entry.length as usize];
+                    let result = ReadNorFlash::read(flash, entry.offset, &mut bytes);
+                    let same = match &entry.data {
+                        TraceData::None => true,
+                        TraceData::Hash(hash) => *hash == fnv1a(&bytes),
+                        TraceData::Full(data) => *data == bytes,
+                    };
+                    if result.is_ok() && !same {
+                        return Err(ReplayError::ReadMismatch { index });
+                    }
+                    result
+                }
+                TraceOp::Write => {
+                    let TraceData::Full(data) = &entry.data else {
+                        return Err(ReplayError::MissingData { index });
+                    };
+                    NorFlash::write(flash, entry.offset, data)
+                }
+                TraceOp::Erase { to } => NorFlash::erase(flash, entry.offset, to),
+                TraceOp::PowerCycle => {
+                    flash.power_cycle();
+                    Ok(())
+                }
+            };
+            flash.set_power_cut(None);
+            let actual = TraceResult::from(&result);
+            if actual != entry.result {
+                return Err(ReplayError::Diverged {
+                    index,
+                    expected: entry.result.clone(),
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GEOMETRY: Geometry = Geometry {
+        read_size: 1,
+        write_size: 4,
+        erase_size: 256,
+        capacity: 1024,
+    };
+
+    fn entries() -> Vec<TraceEntry> {
+        let entry = |op, offset, length, data, result| TraceEntry {
+            op,
+            offset,
+            length,
+            data,
+            result,
+        };
+        vec![
+            entry(
+                TraceOp::Erase { to: 256 },
+                0,
+                256,
+                TraceData::None,
+                TraceResult::Ok,
+            ),
+            entry(
+                TraceOp::Write,
+                4,
+                3,
+                TraceData::Full(vec![1, 2, 3]),
+                TraceResult::PowerLoss { offset: 5 },
+            ),
+            entry(TraceOp::PowerCycle, 0, 0, TraceData::None, TraceResult::Ok),
+            entry(
+                TraceOp::Read,
+                0,
+                8,
+                TraceData::Hash(fnv1a(&[0xFF; 8])),
+                TraceResult::Failed("ECC error reading interrupted word".into()),
+            ),
+        ]
+    }
+
+    fn journal_bytes(entries: &[TraceEntry]) -> Vec<u8> {
+        let mut bytes = encode_header(GEOMETRY);
+        entries
+            .iter()
+            .for_each(|entry| bytes.extend_from_slice(&encode(entry)));
+        bytes
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let entries = entries();
+        let journal = Journal::from_bytes(&journal_bytes(&entries)).unwrap();
+        assert_eq!(journal.geometry, GEOMETRY);
+        assert_eq!(journal.entries, entries);
+    }
+
+    #[test]
+    fn failed_message_is_truncated_on_char_boundary() {
+        let message = "é".repeat(u16::MAX as usize);
+        let bytes = encode(&TraceEntry {
+            op: TraceOp::Write,
+            offset: 0,
+            length: 0,
+            data: TraceData::None,
+            result: TraceResult::Failed(message.clone()),
+        });
+        let entry = decode(&mut Cursor::new(&bytes)).unwrap();
+        let TraceResult::Failed(decoded) = entry.result else {
+            panic!("expected failed result");
+        };
+        assert_eq!(decoded.len(), u16::MAX as usize - 1);
+        assert!(message.starts_with(&decoded));
+    }
+
+    #[test]
+    fn malformed_journal() {
+        assert!(matches!(
+            Journal::from_bytes(b"FMJ0"),
+            Err(JournalError::BadMagic)
+        ));
+        assert!(matches!(
+            Journal::from_bytes(&JOURNAL_MAGIC[..2]),
+            Err(JournalError::Truncated { position: 0 })
+        ));
+
+        let mut bytes = journal_bytes(&entries());
+        bytes.pop();
+        assert!(matches!(
+            Journal::from_bytes(&bytes),
+            Err(JournalError::Truncated { .. })
+        ));
+
+        // 数据类型3无定义
+        let header = encode_header(GEOMETRY).len();
+        let mut bytes = journal_bytes(&entries()[..1]);
+        bytes[header] |= 0b11 << 2;
+        assert!(matches!(
+            Journal::from_bytes(&bytes),
+            Err(JournalError::InvalidEntry { position }) if position == header
+        ));
+    }
+
+    #[test]
+    fn invalid_erase_replays_faithfully() {
+        let mut flash = FlashMock::<1, 4, 256>::new_in_memory(1024).unwrap();
+        flash.start_trace::<&Path>(TraceDetail::Full, None).unwrap();
+        assert!(flash.erase(512, 256).is_err());
+        flash.erase(0, 256).unwrap();
+        let entries = flash.stop_trace().unwrap();
+        let journal = Journal::from_bytes(&journal_bytes(&entries)).unwrap();
+        assert_eq!(journal.entries[0].op, TraceOp::Erase { to: 256 });
+        assert_eq!(journal.entries[0].length, 0);
+
+        let mut fresh = FlashMock::<1, 4, 256>::new_in_memory(1024).unwrap();
+        journal.replay(&mut fresh).unwrap();
+    }
+
+    #[test]
+    fn overflowing_ranges_are_rejected() {
+        let entry = |op, result| TraceEntry {
+            op,
+            offset: 0xFFFF_FF00,
+            length: 0x200,
+            data: TraceData::None,
+            result,
+        };
+        let header = encode_header(GEOMETRY).len();
+        for entry in [
+            entry(TraceOp::Erase { to: 0x100 }, TraceResult::Ok),
+            entry(TraceOp::Read, TraceResult::Ok),
+            entry(TraceOp::Write, TraceResult::PowerLoss { offset: 0 }),
+        ] {
+            assert!(matches!(
+                Journal::from_bytes(&journal_bytes(&[entry])),
+                Err(JournalError::InvalidRange { position }) if position == header
+            ));
+        }
+        // 越界失败的读写照常保留，重放时同样失败
+        let failed = entry(TraceOp::Read, TraceResult::Failed("out of bounds".into()));
+        assert!(Journal::from_bytes(&journal_bytes(&[failed])).is_ok());
+    }
+}